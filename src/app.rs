@@ -1,53 +1,225 @@
 use ratatui::{
     buffer::Buffer,
-    layout::{Constraint, Layout, Rect},
-    style::{
-        palette::tailwind::{AMBER, TEAL},
-        Color, Modifier, Style, Stylize,
-    },
+    layout::{Constraint, Flex, Layout, Position, Rect},
+    style::{Modifier, Style, Stylize},
     symbols::{self},
-    text::Line,
+    text::{Line, Span},
     widgets::{
-        Block, Borders, HighlightSpacing, LineGauge, List, ListItem, Paragraph, StatefulWidget,
-        Widget, Wrap,
+        canvas::{self, Canvas},
+        Block, Borders, Clear, HighlightSpacing, LineGauge, List, ListItem, Paragraph,
+        Scrollbar, ScrollbarOrientation, ScrollbarState, Sparkline, StatefulWidget, Tabs, Widget,
     },
 };
 use ratatui::{
-    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    crossterm::{
+        event::{
+            self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent,
+            KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+        },
+        execute,
+    },
     widgets::ListState,
     DefaultTerminal,
 };
+use std::collections::VecDeque;
+use std::io::stdout;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::sound_manager::SoundManager;
+use crate::config::{Config, ConfigError, ConfigWatch};
+use crate::control::{Command, ControlRequest, Response, Status};
+use crate::daemon::DaemonClient;
+use crate::history::{restore_commands, History};
+use crate::keymap::{Action, Keymap};
+use crate::library::{tags_match, Library};
+use crate::meter::{decibels, meter_ratio, Level};
+use crate::sound_manager::{SoundData, SoundManager};
+use crate::theme::Theme;
+use crate::view::{Filter, ListView, Scope};
 use cli_log::*;
 use color_eyre::Result;
 
+// How often the mix is refreshed from the daemon and the remote commands applied when idle
+const TICK: Duration = Duration::from_millis(100);
+const PAN_STEP: f32 = 0.1;
+// How long a message stays in the header
+const MESSAGE_TIME: Duration = Duration::from_secs(3);
+// Master levels kept for the level history of the mixer
+const MASTER_HISTORY: usize = 200;
+// Smallest peak the waveform is scaled to, so that near silence stays flat
+const WAVE_FLOOR: f32 = 0.01;
+// Mixer rows of a layer: its name, volume gauge and level meter
+const LAYER_ROWS: u16 = 3;
+// Rows of the master level history, with its title
+const MASTER_ROWS: u16 = 4;
+// Below this width the mixer goes under the list
+const NARROW_WIDTH: u16 = 80;
+// Below this height the header takes one line and the footer is hidden
+const SHORT_HEIGHT: u16 = 20;
+
+// Categories shown before the category folders
+const VIRTUAL_CATEGORIES: [&str; 2] = ["Favourites", "Recent"];
+
+/// Text being typed, the keys then go to it
+struct Input {
+    kind: InputKind,
+    text: String,
+}
+
+enum InputKind {
+    /// Filters the list as the text is typed
+    Search,
+    /// Tag filter, applied as it is typed too
+    Filter,
+    /// Tags of the sound with this path
+    Tags(String),
+}
+
+/// Panel receiving the selection keys
+#[derive(PartialEq)]
+enum Focus {
+    List,
+    Mixer,
+}
+
 pub struct App {
     exit: bool,
     state: ListState,
     sound_manager: SoundManager,
+    // Index in the virtual categories then the category folders, None shows every sound
     category: Option<usize>,
+    library: Library,
+    // Path of the selected sound, the list selection follows it when the view changes
+    selected: Option<String>,
+    focus: Focus,
+    // Selected layer of the mixer, in sink order
+    layer: usize,
+    // First layer shown when the mixer is too small for all of them
+    mixer_offset: usize,
+    input: Option<Input>,
+    // Scroll of the help overlay, None when closed
+    help: Option<u16>,
+    // Tag filter narrowing the list, see `library::tags_match`
+    tag_filter: Option<String>,
+    // Set when attached to a daemon, sound_manager is then a silent mirror
+    daemon: Option<DaemonClient>,
+    // Commands coming from the other interfaces (MPRIS...)
+    requests: Receiver<ControlRequest>,
+    requests_tx: Sender<ControlRequest>,
+    keymap: Keymap,
+    theme: Theme,
+    volume_step: f32,
+    // config.toml and when it was last modified, reloaded when this changes
+    config: ConfigWatch,
+    // Only shows a status line
+    compact: bool,
+    // Changes of the mix made from this interface
+    history: History,
+    // Feedback shown in the header for a while
+    message: Option<(String, Instant)>,
+    // Where the last frame drew the list and the volume gauges, for the mouse
+    list_area: Rect,
+    mixer_area: Rect,
+    gauges: Vec<(Rect, String)>,
+    // Layer whose gauge is being dragged
+    dragged: Option<String>,
+    // Last master levels, in percents of the meter, the newest last
+    master_history: VecDeque<u64>,
+    history_sampled: Instant,
 }
 
 impl App {
     pub fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
+        execute!(stdout(), EnableMouseCapture)?;
+        let result = self.run_loop(&mut terminal);
+        // Also when the loop failed, so that the terminal and the session are left clean
+        self.sound_manager.save_session();
+        let disabled = execute!(stdout(), DisableMouseCapture);
+        result?;
+        disabled?;
+        Ok(())
+    }
+
+    // Adds the master level to its history every tick, whatever is drawn
+    fn sample_history(&mut self) {
+        if self.history_sampled.elapsed() < TICK {
+            return;
+        }
+        self.history_sampled = Instant::now();
+        let master = Level::mix(&self.sound_manager.levels());
+        if self.master_history.len() == MASTER_HISTORY {
+            self.master_history.pop_front();
+        }
+        self.master_history
+            .push_back((meter_ratio(master.rms) * 100.0) as u64);
+    }
+
+    fn run_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
         while !self.exit {
-            terminal.draw(|frame| frame.render_widget(&mut self, frame.area()))?;
-            if let Event::Key(key) = event::read()? {
-                self.handle_key(key);
-            };
+            terminal.draw(|frame| frame.render_widget(&mut *self, frame.area()))?;
+            if event::poll(TICK)? {
+                match event::read()? {
+                    Event::Key(key) => self.handle_key(key),
+                    Event::Mouse(mouse) => self.handle_mouse(mouse),
+                    _ => {}
+                };
+            } else if self.daemon.is_some() {
+                self.send(Command::Status);
+            }
+            self.serve_requests();
+            self.sample_history();
+            self.sound_manager.autosave();
+            self.reload_config();
         }
         Ok(())
     }
 
-    pub fn new(sound_manager: SoundManager) -> Self {
-        App {
+    pub fn new(
+        sound_manager: SoundManager,
+        daemon: Option<DaemonClient>,
+        config: &Config,
+        config_file: PathBuf,
+        compact: bool,
+    ) -> Result<Self, ConfigError> {
+        let (requests_tx, requests) = mpsc::channel();
+        let library = Library::load(sound_manager.dirs().library());
+        Ok(App {
             exit: false,
             state: ListState::default(),
             sound_manager,
             category: None,
-        }
+            library,
+            selected: None,
+            focus: Focus::List,
+            layer: 0,
+            mixer_offset: 0,
+            input: None,
+            help: None,
+            tag_filter: None,
+            daemon,
+            requests,
+            requests_tx,
+            keymap: config.keymap(&config_file)?,
+            theme: config.theme(&config_file)?,
+            volume_step: config.volume_step,
+            config: ConfigWatch::new(config_file, config),
+            compact,
+            history: History::default(),
+            message: None,
+            list_area: Rect::default(),
+            mixer_area: Rect::default(),
+            gauges: vec![],
+            dragged: None,
+            master_history: VecDeque::with_capacity(MASTER_HISTORY),
+            history_sampled: Instant::now(),
+        })
+    }
+
+    /// To give to the interfaces controlling this app
+    pub fn control_sender(&self) -> Sender<ControlRequest> {
+        self.requests_tx.clone()
     }
 
     //----Getters
@@ -65,189 +237,740 @@ impl App {
         if key.kind != KeyEventKind::Press {
             return;
         }
-        let ctrl_pressed = key.modifiers.contains(KeyModifiers::CONTROL);
+        if let Some(scroll) = self.help {
+            self.help = match key.code {
+                KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('?') => None,
+                KeyCode::Down | KeyCode::Char('j') => Some(scroll.saturating_add(1)),
+                KeyCode::Up | KeyCode::Char('k') => Some(scroll.saturating_sub(1)),
+                KeyCode::PageDown => Some(scroll.saturating_add(10)),
+                KeyCode::PageUp => Some(scroll.saturating_sub(10)),
+                KeyCode::Home => Some(0),
+                _ => Some(scroll),
+            };
+            return;
+        }
+        if self.input.is_some() {
+            self.handle_input_key(key);
+            return;
+        }
+        if let Some(action) = self.keymap.action(&key) {
+            self.perform(action);
+        }
+    }
+
+    // While typing the keys are text, except the ones moving the selection
+    fn handle_input_key(&mut self, key: KeyEvent) {
+        let Some(input) = &mut self.input else {
+            return;
+        };
         match key.code {
-            KeyCode::Char('h') | KeyCode::Left => self.change_volume(-0.02, ctrl_pressed),
-            KeyCode::Char('i') | KeyCode::Right => self.change_volume(0.02, ctrl_pressed),
-            KeyCode::Char('j') | KeyCode::Down => self.select_next(),
-            KeyCode::Char('k') | KeyCode::Up => self.select_previous(),
-            KeyCode::Char(' ') => {self.sound_manager.toggle_pause_play();},
-            KeyCode::Char('G') | KeyCode::End => self.select_last(),
-            KeyCode::Char('q') => self.exit = true,
-            KeyCode::Char('c') => self.swicth_category(),
-            KeyCode::Char('s') => {
-                let _ = self.sound_manager.save();
-            }
-            KeyCode::Enter => self.toogle_selected_sound(),
+            KeyCode::Esc => {
+                if let InputKind::Filter = input.kind {
+                    self.tag_filter = None;
+                }
+                self.input = None;
+                self.sync_selection();
+            }
+            KeyCode::Enter => {
+                let input = self.input.take().expect("typing");
+                match input.kind {
+                    InputKind::Search => self.toogle_selected_sound(),
+                    InputKind::Filter => {
+                        self.tag_filter = Some(input.text).filter(|t| !t.trim().is_empty())
+                    }
+                    InputKind::Tags(path) => self.library.set_tags(&path, &input.text),
+                }
+                self.sync_selection();
+            }
+            KeyCode::Backspace => {
+                input.text.pop();
+                self.input_changed();
+            }
+            KeyCode::Down => self.select_next(),
+            KeyCode::Up => self.select_previous(),
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                input.text.push(c);
+                self.input_changed();
+            }
+            _ => {}
+        }
+    }
+
+    fn start_input(&mut self, kind: InputKind, text: String) {
+        self.focus = Focus::List;
+        self.input = Some(Input { kind, text });
+        self.input_changed();
+    }
+
+    // The search and the tag filter apply as they are typed
+    fn input_changed(&mut self) {
+        match self.input.as_ref().map(|i| &i.kind) {
+            Some(InputKind::Search) => self.select(0),
+            Some(InputKind::Filter) => {
+                self.tag_filter = self.input.as_ref().map(|i| i.text.clone());
+                self.sync_selection();
+            }
+            _ => {}
+        }
+    }
+
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::VolumeDown => self.change_sound_volume(-self.volume_step),
+            Action::VolumeUp => self.change_sound_volume(self.volume_step),
+            Action::MasterDown => self.change_master_volume(-self.volume_step),
+            Action::MasterUp => self.change_master_volume(self.volume_step),
+            Action::Mute => self.toggle_mute(),
+            Action::PanLeft => self.change_pan(-PAN_STEP),
+            Action::PanRight => self.change_pan(PAN_STEP),
+            Action::SelectNext => self.select_next(),
+            Action::SelectPrevious => self.select_previous(),
+            Action::TogglePause => self.send(Command::TogglePause),
+            Action::SelectLast => self.select_last(),
+            Action::SwitchFocus => self.switch_focus(),
+            Action::Quit => self.exit = true,
+            Action::NextCategory => self.swicth_category(),
+            Action::PreviousCategory => self.previous_category(),
+            Action::ShowAll => self.set_category(None),
+            Action::ShowCategory(n) => {
+                if n as usize <= self.category_count() {
+                    self.set_category((n as usize).checked_sub(1));
+                }
+            }
+            Action::Search => self.start_input(InputKind::Search, String::new()),
+            Action::FilterTags => {
+                let filter = self.tag_filter.clone().unwrap_or_default();
+                self.start_input(InputKind::Filter, filter);
+            }
+            Action::EditTags => {
+                if let Some(path) = self.selected_path() {
+                    let tags = self.library.tags(&path).join(" ");
+                    self.start_input(InputKind::Tags(path), tags);
+                }
+            }
+            Action::SaveFilter => self.save_filter(),
+            Action::DeleteFilter => self.delete_filter(),
+            Action::Save => self.send(Command::Save { preset: None }),
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+            Action::Compact => self.compact = !self.compact,
+            Action::Help => self.help = Some(0),
+            Action::ToggleSound => self.toogle_selected_sound(),
+            Action::RemoveSound => self.remove_selected_sound(),
+            Action::Favourite => {
+                if let Some(path) = self.selected_path() {
+                    self.library.toggle_favourite(&path);
+                    self.sync_selection();
+                }
+            }
+        }
+    }
+
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        let position = Position::new(mouse.column, mouse.row);
+        match mouse.kind {
+            // The wheel moves the selection of the panel under the pointer
+            MouseEventKind::ScrollDown | MouseEventKind::ScrollUp => {
+                if self.mixer_area.contains(position) && !self.layers().is_empty() {
+                    self.focus = Focus::Mixer;
+                } else if self.list_area.contains(position) {
+                    self.focus = Focus::List;
+                }
+                if mouse.kind == MouseEventKind::ScrollDown {
+                    self.select_next();
+                } else {
+                    self.select_previous();
+                }
+            }
+            MouseEventKind::Down(MouseButton::Left) => {
+                if self.list_area.contains(position) {
+                    let index = self.state.offset() + (mouse.row - self.list_area.y) as usize;
+                    if index < self.view().len() {
+                        self.select(index);
+                        self.toogle_selected_sound();
+                    }
+                } else if let Some((area, path)) =
+                    self.gauges.iter().find(|(area, _)| area.contains(position))
+                {
+                    let volume = gauge_volume(*area, mouse.column);
+                    let path = path.clone();
+                    self.focus = Focus::Mixer;
+                    self.layer = self.layers().iter().position(|l| l.source == path).unwrap_or(0);
+                    self.dragged = Some(path.clone());
+                    self.send(Command::Volume { path, volume });
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                if let Some(path) = self.dragged.clone() {
+                    // The pointer may leave the gauge row while dragging
+                    if let Some((area, _)) = self.gauges.iter().find(|(_, p)| *p == path) {
+                        let volume = gauge_volume(*area, mouse.column);
+                        self.send(Command::Volume { path, volume });
+                    }
+                }
+            }
+            MouseEventKind::Up(MouseButton::Left) => self.dragged = None,
             _ => {}
         }
     }
 
+    // Runs a command, the changes of the mix can then be undone
+    fn send(&mut self, command: Command) {
+        if let Some(label) = self.change_label(&command) {
+            self.history.record(label, self.sound_manager.status());
+        }
+        self.dispatch(command);
+    }
+
+    // What a command changes, None if it does not change the mix
+    fn change_label(&self, command: &Command) -> Option<String> {
+        let name = |path: &str| {
+            self.sound_manager
+                .get_sound_by_path(path)
+                .map_or(path.to_string(), |s| s.name().to_string())
+        };
+        Some(match command {
+            Command::Toggle { path } if self.sound_manager.is_sound_playing(path) => {
+                format!("removing {}", name(path))
+            }
+            Command::Toggle { path } => format!("adding {}", name(path)),
+            Command::Volume { path, .. } => format!("volume of {}", name(path)),
+            Command::Mute { path, muted: true } => format!("muting {}", name(path)),
+            Command::Mute { path, muted: false } => format!("unmuting {}", name(path)),
+            Command::Pan { path, .. } => format!("pan of {}", name(path)),
+            Command::Master { .. } => "master volume".to_string(),
+            Command::Load { preset } => format!("loading {}", preset),
+            _ => return None,
+        })
+    }
+
+    fn undo(&mut self) {
+        match self.history.undo(self.sound_manager.status()) {
+            Some(change) => {
+                self.restore(&change.before);
+                self.show_message(format!("Undid {}", change.label));
+            }
+            None => self.show_message("Nothing to undo".to_string()),
+        }
+    }
+
+    fn redo(&mut self) {
+        match self.history.redo(self.sound_manager.status()) {
+            Some(change) => {
+                self.restore(&change.before);
+                self.show_message(format!("Redid {}", change.label));
+            }
+            None => self.show_message("Nothing to redo".to_string()),
+        }
+    }
+
+    // Brings the mix back to a previous state, without recording it
+    fn restore(&mut self, target: &Status) {
+        for command in restore_commands(&self.sound_manager.status(), target) {
+            self.dispatch(command);
+        }
+    }
+
+    // Applies what can change at once in config.toml when it changed, the sounds
+    // settings being left to the daemon when attached, an invalid config is reported
+    // and the current settings kept
+    fn reload_config(&mut self) {
+        let path = self.config.path().to_path_buf();
+        let reloaded = match self.config.reload() {
+            Some(Ok(reloaded)) => reloaded,
+            Some(Err(e)) => {
+                warn!("{}", e);
+                self.show_message(e.to_string());
+                return;
+            }
+            None => return,
+        };
+        let config = &reloaded.config;
+        match config.keymap(&path).and_then(|keymap| Ok((keymap, config.theme(&path)?))) {
+            Ok((keymap, theme)) => {
+                self.keymap = keymap;
+                self.theme = theme;
+                self.volume_step = config.volume_step;
+                if self.daemon.is_none() {
+                    self.sound_manager.set_volumes(config.volumes());
+                }
+                info!("Reloaded {}", path.display());
+                self.show_message(reloaded.message());
+            }
+            Err(e) => {
+                warn!("{}", e);
+                self.show_message(e.to_string());
+            }
+        }
+    }
+
+    fn show_message(&mut self, message: String) {
+        self.message = Some((message, Instant::now()));
+    }
+
+    // The message while it is recent
+    fn message(&self) -> Option<&str> {
+        self.message
+            .as_ref()
+            .filter(|(_, at)| at.elapsed() < MESSAGE_TIME)
+            .map(|(message, _)| message.as_str())
+    }
+
+    // Runs a command locally or on the daemon we are attached to
+    fn dispatch(&mut self, command: Command) {
+        match &mut self.daemon {
+            Some(daemon) => match daemon.send(&command) {
+                Ok(response) => self.sound_manager.apply_status(&response.status),
+                Err(e) => {
+                    warn!("Lost the daemon: {}", e);
+                    self.exit = true;
+                }
+            },
+            None => {
+                self.sound_manager.execute(&command);
+            }
+        }
+    }
+
+    fn serve_requests(&mut self) {
+        while let Ok(request) = self.requests.try_recv() {
+            // The categories may change, the current one is found again by name
+            let category = self.category.map(|c| self.category_name(c).to_string());
+            let response = self.sound_manager.execute(&request.command);
+            match request.command {
+                Command::Quit => self.exit = true,
+                Command::Rescan => self.rescanned(category.as_deref(), &response),
+                _ => {}
+            }
+            let _ = request.reply.send(response);
+        }
+    }
+
+    // Keeps the category and the selected row after the sound folders were read again
+    fn rescanned(&mut self, category: Option<&str>, response: &Response) {
+        self.category = category
+            .and_then(|name| (0..self.category_count()).find(|c| self.category_name(*c) == name));
+        let view = self.view();
+        let index = match self.selected.as_deref().and_then(|path| view.index_of(path)) {
+            Some(index) => index,
+            // Removed, the row that took its place
+            None => self.state.selected().unwrap_or(0),
+        };
+        self.select(index);
+        self.layer = self.layer.min(self.layers().len().saturating_sub(1));
+        let removed: Vec<String> = response
+            .data
+            .clone()
+            .and_then(|data| serde_json::from_value(data).ok())
+            .unwrap_or_default();
+        if !removed.is_empty() {
+            let names: Vec<&str> =
+                removed.iter().map(|p| p.rsplit('/').next().unwrap_or(p)).collect();
+            self.show_message(format!("Removed from disk: {}", names.join(", ")));
+        }
+    }
+
     fn _select_none(&mut self) {
         self.state.select(None);
+        self.selected = None;
     }
 
     fn select_next(&mut self) {
-        self.state.select_next();
+        match self.focus {
+            Focus::List => self.select(self.state.selected().map_or(0, |i| i + 1)),
+            Focus::Mixer => {
+                self.layer = (self.layer + 1).min(self.layers().len().saturating_sub(1))
+            }
+        }
     }
     fn select_previous(&mut self) {
-        self.state.select_previous();
+        match self.focus {
+            Focus::List => self.select(self.state.selected().unwrap_or(0).saturating_sub(1)),
+            Focus::Mixer => self.layer = self.layer.saturating_sub(1),
+        }
     }
 
     fn _select_first(&mut self) {
-        self.state.select_first();
+        self.select(0);
     }
 
     fn select_last(&mut self) {
-        /*if let Some(index) = self.list.items().len().checked_sub(1) {
-            self.state.select(Some(index));
-        }*/
+        match self.focus {
+            Focus::List => self.select(usize::MAX),
+            Focus::Mixer => self.layer = self.layers().len().saturating_sub(1),
+        }
+    }
+
+    // Favourites and Recent, then the saved tag filters, then the category folders
+    fn category_count(&self) -> usize {
+        VIRTUAL_CATEGORIES.len() + self.library.filters.len() + self.sound_manager.categories().len()
+    }
+
+    fn category_name(&self, category: usize) -> &str {
+        match self.scope(category) {
+            Scope::Category(name) | Scope::Tags(name) => name,
+            _ => VIRTUAL_CATEGORIES[category],
+        }
+    }
+
+    fn scope(&self, category: usize) -> Scope<'_> {
+        let filters = &self.library.filters;
+        match category {
+            0 => Scope::Sounds(&self.library.favourites),
+            1 => Scope::Sounds(&self.library.recent),
+            c if c - VIRTUAL_CATEGORIES.len() < filters.len() => {
+                Scope::Tags(&filters[c - VIRTUAL_CATEGORIES.len()])
+            }
+            c => Scope::Category(
+                &self.sound_manager.categories()[c - VIRTUAL_CATEGORIES.len() - filters.len()],
+            ),
+        }
+    }
+
+    /// Saves the tag filter as a category
+    fn save_filter(&mut self) {
+        match self.tag_filter.take() {
+            Some(filter) => {
+                let filter = filter.trim();
+                self.library.save_filter(filter);
+                let index = self.library.filters.iter().position(|f| *f == filter);
+                self.set_category(index.map(|i| i + VIRTUAL_CATEGORIES.len()));
+            }
+            None => self.show_message("No tag filter to save".to_string()),
+        }
+    }
+
+    /// Deletes the saved filter shown
+    fn delete_filter(&mut self) {
+        let filter = match self.category.map(|category| self.scope(category)) {
+            Some(Scope::Tags(filter)) => filter.to_string(),
+            _ => {
+                self.show_message("Not a saved filter".to_string());
+                return;
+            }
+        };
+        self.library.remove_filter(&filter);
+        self.set_category(None);
+        self.show_message(format!("Deleted the filter {}", filter));
     }
 
     fn swicth_category(&mut self) {
-        let categories = self.sound_manager.categories();
-        self.category = match self.category {
+        let count = self.category_count();
+        let category = match self.category {
             None => Some(0),
             Some(i) => {
-                if i + 1 == categories.len() {
+                if i + 1 == count {
                     None
                 } else {
                     Some(i + 1)
                 }
             }
+        };
+        self.set_category(category);
+    }
+
+    fn previous_category(&mut self) {
+        let count = self.category_count();
+        let category = match self.category {
+            None => count.checked_sub(1),
+            Some(0) => None,
+            Some(i) => Some(i - 1),
+        };
+        self.set_category(category);
+    }
+
+    fn set_category(&mut self, category: Option<usize>) {
+        self.category = category;
+        self.sync_selection();
+    }
+    fn switch_focus(&mut self) {
+        self.focus = match self.focus {
+            Focus::List if !self.layers().is_empty() => Focus::Mixer,
+            _ => Focus::List,
         }
     }
-    fn change_volume(&mut self, volume_offset: f32, master: bool) {
-        if master {
-            self.change_master_volume(volume_offset);
-        } else {
-            self.change_sound_volume(volume_offset);
+
+    fn layers(&self) -> Vec<SoundData> {
+        self.sound_manager.layers()
+    }
+
+    // Sound the actions apply to, the selected row of the focused panel
+    fn selected_path(&self) -> Option<String> {
+        match self.focus {
+            Focus::List => self.selected.clone(),
+            Focus::Mixer => self.layers().get(self.layer).map(|l| l.source.clone()),
         }
     }
 
     fn change_sound_volume(&mut self, volume_offset: f32) {
-        if let Some(index) = self.state.selected() {
-            let path = self
-                .sound_manager
-                .get_sound_path_by_index_and_category(index, self.category)
-                .to_string();
-            self.sound_manager.adjust_sound_volume(&path, volume_offset);
+        if let Some(path) = self.selected_path() {
+            if let Some(sound) = self.sound_manager.get_sound_by_path(&path) {
+                let volume = (sound.volume() + volume_offset).clamp(0.0, 1.0);
+                self.send(Command::Volume { path, volume });
+            }
+        }
+    }
+
+    fn toggle_mute(&mut self) {
+        if let Some(path) = self.selected_path() {
+            if let Some(sound) = self.sound_manager.get_sound_by_path(&path) {
+                let muted = !sound.muted();
+                self.send(Command::Mute { path, muted });
+            }
+        }
+    }
+
+    fn change_pan(&mut self, pan_offset: f32) {
+        if let Some(path) = self.selected_path() {
+            if let Some(sound) = self.sound_manager.get_sound_by_path(&path) {
+                let pan = (sound.pan() + pan_offset).clamp(-1.0, 1.0);
+                self.send(Command::Pan { path, pan });
+            }
+        }
+    }
+
+    /// What the sounds list shows with the current category and search
+    fn view(&self) -> ListView {
+        let search = match &self.input {
+            Some(Input {
+                kind: InputKind::Search,
+                text,
+            }) => Some(text.as_str()),
+            _ => None,
+        };
+        let filter = Filter {
+            scope: self.category.map_or(Scope::All, |c| self.scope(c)),
+            search,
+            tags: self.tag_filter.as_deref(),
+        };
+        ListView::new(self.sound_manager.get_sound_list(), &self.library, &filter)
+    }
+
+    /// Selects a row of the view, clamped to the visible rows
+    fn select(&mut self, index: usize) {
+        let view = self.view();
+        let index = (!view.is_empty()).then(|| index.min(view.len() - 1));
+        self.state.select(index);
+        self.selected = index.and_then(|i| view.path(i)).map(|p| p.to_string());
+    }
+
+    /// Keeps the selected sound selected after the view changed, the first row otherwise
+    fn sync_selection(&mut self) {
+        let view = self.view();
+        let index = self
+            .selected
+            .as_deref()
+            .and_then(|path| view.index_of(path))
+            .unwrap_or(0);
+        self.select(index);
+    }
+
+    fn remove_selected_sound(&mut self) {
+        if let Some(path) = self.selected_path() {
+            if self.sound_manager.is_sound_playing(&path) {
+                self.send(Command::Toggle { path });
+            }
         }
     }
 
     fn change_master_volume(&mut self, volume_offset: f32) {
-        self.sound_manager.adjust_master_volume(volume_offset);
+        let volume = self.sound_manager.master_volume() + volume_offset;
+        self.send(Command::Master { volume });
     }
 
     fn toogle_selected_sound(&mut self) {
-        if let Some(index) = self.state.selected() {
-            let path = self
-                .sound_manager
-                .get_sound_path_by_index_and_category(index, self.category)
-                .to_string();
+        if let Some(path) = self.selected_path() {
             info!("Toggling sound: {}", path);
-            let _ = self.sound_manager.toggle_sound(&path);
+            if !self.sound_manager.is_sound_playing(&path) {
+                self.library.add_recent(&path);
+            }
+            self.send(Command::Toggle { path });
         }
     }
 }
 
-const TODO_HEADER_STYLE: Style = Style::new()
-    .fg(TEAL.c100)
-    .bg(TEAL.c800)
-    .add_modifier(Modifier::BOLD);
-const NORMAL_ROW_BG: Color = TEAL.c900;
-const ALT_ROW_BG_COLOR: Color = TEAL.c800;
-const MIXER_BORDERS_STYLE: Style = Style::new()
-    .fg(TEAL.c100)
-    .bg(TEAL.c500)
-    .add_modifier(Modifier::BOLD);
-const YELLOW: Color = AMBER.c100;
-const SELECTED_STYLE: Style = Style::new().bg(TEAL.c600).add_modifier(Modifier::BOLD);
-//const EDIT_VALUE_COLOR: Color = AMBER.c500;
-/*const EDIT_STYLE: Style = Style::new()
-    .bg(EDIT_ROW_COLOR)
-    .add_modifier(Modifier::BOLD)
-    .fg(AMBER.c100);
-const EDIT_VALUE_STYLE: Style = Style::new()
-    .bg(EDIT_VALUE_COLOR)
-    .add_modifier(Modifier::BOLD)
-    .fg(AMBER.c100);
-const TEXT_FG_COLOR: Color = TEAL.c200;
-const TEXT_STYLE: Style = Style::new().fg(TEXT_FG_COLOR);*/
-
 impl App {
     //Renders header
+    #[allow(clippy::unit_arg)]
     fn render_header(&self, area: Rect, buf: &mut Buffer) {
-        let text = format!("SerenIT\n{}",if self.sound_manager.is_paused() {"[Paused]"} else {""});
+        let mut text = format!(
+            "SerenIT{}Master {:.0}% {}{}",
+            // A single line in short terminals
+            if area.height < 2 { " - " } else { "\n" },
+            self.sound_manager.master_volume() * 100.0,
+            if self.sound_manager.is_paused() {"[Paused]"} else {""},
+            if self.daemon.is_some() {"[Attached]"} else {""}
+        );
+        if let Some(message) = self.message() {
+            text += &format!(" | {}", message);
+        }
         Arc::new(
             Paragraph::new(text)
                 .bold()
                 .centered()
-                .bg(TEAL.c500)
-                .fg(YELLOW)
+                .bg(self.theme.bar)
+                .fg(self.theme.bar_text)
                 .render(area, buf),
         );
     }
 
+    //Renders the whole compact view, a single line
+    fn render_compact(&mut self, area: Rect, buf: &mut Buffer) {
+        // Nothing to click on
+        self.list_area = Rect::default();
+        self.mixer_area = Rect::default();
+        self.gauges.clear();
+        let mut text = self.sound_manager.status().summary();
+        if self.daemon.is_some() {
+            text += " [Attached]";
+        }
+        match self.message() {
+            Some(message) => text += &format!(" | {}", message),
+            None => text += &format!(" | {} view", self.keymap.key(Action::Compact)),
+        }
+        Paragraph::new(text)
+            .bold()
+            .bg(self.theme.bar)
+            .fg(self.theme.bar_text)
+            .render(Rect { height: area.height.min(1), ..area }, buf);
+    }
+
     //Renders footer
     fn render_footer(&self, area: Rect, buf: &mut Buffer) {
-        let text = "Add/Remove the selected sound with Enter, pause/play with space\n\
-            -/+ to adjust the volume, ctrl & -/+ to adjust the master volume\n\
-            's' to save, 'c' to swicth category, 'q' to quit";
+        let key = |action| self.keymap.key(action);
+        let text = format!(
+            "{} add/remove, {} pause/play, {}/{} volume, {}/{} master volume, {} mixer\n\
+            {} search, {} category, {} save, {} help, {} quit (or detach from the daemon)",
+            key(Action::ToggleSound),
+            key(Action::TogglePause),
+            key(Action::VolumeDown),
+            key(Action::VolumeUp),
+            key(Action::MasterDown),
+            key(Action::MasterUp),
+            key(Action::SwitchFocus),
+            key(Action::Search),
+            key(Action::NextCategory),
+            key(Action::Save),
+            key(Action::Help),
+            key(Action::Quit),
+        );
         Paragraph::new(text)
             .centered()
-            .bg(TEAL.c500)
-            .fg(YELLOW)
+            .bg(self.theme.bar)
+            .fg(self.theme.bar_text)
             .bold()
             .render(area, buf);
     }
 
+    //Renders the help overlay, every action with its keys
+    fn render_help(&mut self, area: Rect, buf: &mut Buffer) {
+        let mut lines: Vec<Line> = vec![];
+        for context in Action::CONTEXTS {
+            let header = self.theme.header().fg(self.theme.accent);
+            lines.push(Line::styled(context.to_uppercase(), header));
+            for action in Action::ALL.iter().filter(|a| a.context() == context) {
+                let keys: Vec<String> = self
+                    .keymap
+                    .keys(*action)
+                    .iter()
+                    .map(|k| k.to_string())
+                    .collect();
+                lines.push(self.help_line(&keys.join(" "), action.description()));
+            }
+            // Keys of the text inputs cannot be changed
+            if context == "Search" {
+                lines.push(self.help_line("Enter", "Apply, toggles the first result of a search"));
+                lines.push(self.help_line("Esc", "Cancel"));
+                lines.push(self.help_line("↑ ↓", "Move in the results"));
+            }
+            lines.push(Line::raw(""));
+        }
+
+        let popup = centered(area, 80, 80);
+        let block = Block::bordered()
+            .title(Line::raw(" Help ").centered())
+            .title_bottom(Line::raw(" ↑↓ to scroll, Esc to close ").centered())
+            .border_style(self.theme.borders())
+            .bg(self.theme.background);
+        let height = block.inner(popup).height;
+        let scroll = self
+            .help
+            .unwrap_or(0)
+            .min((lines.len() as u16).saturating_sub(height));
+        self.help = Some(scroll);
+
+        Clear.render(popup, buf);
+        Paragraph::new(lines)
+            .block(block)
+            .scroll((scroll, 0))
+            .render(popup, buf);
+    }
+
     //Renders left list
     fn render_list(&mut self, area: Rect, buf: &mut Buffer) {
+        // The selected sound may have left the view (remote changes...)
+        if self.selected.is_some() {
+            self.sync_selection();
+        }
         //Category
-        let categories = self.sound_manager.categories();
-        let category_text = match self.category {
-            Some(i) => format!(
-                "{}/{} {}",
-                i + 1,
-                categories.len(),
-                categories[i].to_uppercase()
-            ),
-            None => "All".to_string(),
-        };
-        let category_line = Line::styled(
-            "Category: ".to_string() + &category_text,
-            TODO_HEADER_STYLE.fg(if self.category.is_some() {
-                YELLOW
-            } else {
-                TEAL.c100
-            }),
-        )
+        let category_line = match &self.input {
+            Some(input) => {
+                let prompt = match &input.kind {
+                    InputKind::Search => "Search".to_string(),
+                    InputKind::Filter => format!("Tags ({})", self.library.all_tags().join(" ")),
+                    InputKind::Tags(path) => format!("Tags of {}", path),
+                };
+                Line::styled(
+                    format!("{}: {}_", prompt, input.text),
+                    self.theme.header().fg(self.theme.accent),
+                )
+            }
+            None => match &self.tag_filter {
+                Some(filter) => Line::styled(
+                    format!("{} sounds tagged {}", self.view().len(), filter),
+                    self.theme.header().fg(self.theme.accent),
+                ),
+                None => Line::styled(
+                    format!("{} sounds", self.view().len()),
+                    self.theme.header(),
+                ),
+            },
+        }
         .centered();
 
         // Sounds
+        let show_category = self
+            .category
+            .is_none_or(|c| !matches!(self.scope(c), Scope::Category(_)))
+            || self.input.is_some();
         let items: Vec<ListItem> = self
-            .sound_manager
-            .get_sound_list()
+            .view()
+            .rows()
             .iter()
-            .filter(|s| {
-                if let Some(c) = self.category {
-                    s.category() == self.sound_manager.categories()[c]
-                } else {
-                    true
-                }
-            })
             .enumerate()
-            .map(|(i, s)| {
-                let color = alternate_colors(i);
-                let displayed_name = if self.category.is_none() {
-                    format!("[{}] {}", s.category().to_uppercase(), s.name())
-                } else {
-                    s.name().to_string()
-                };
-                let mut item = ListItem::from(displayed_name).bg(color);
-                if self.sound_manager.is_sound_playing(s.path()) {
-                    item = item.add_modifier(Modifier::BOLD).fg(AMBER.c100);
+            .map(|(i, row)| {
+                let color = self.theme.row(i);
+                let mut spans = vec![];
+                if self.library.is_favourite(&row.path) {
+                    spans.push(Span::raw("★ "));
+                }
+                if show_category {
+                    spans.push(Span::raw("["));
+                    spans.extend(self.highlight(&row.category, &row.category_matches));
+                    spans.push(Span::raw("] "));
+                }
+                spans.extend(self.highlight(&row.name, &row.name_matches));
+                for (i, tag) in row.tags.iter().enumerate() {
+                    let style = if row.tag_match == Some(i) {
+                        self.theme.matched()
+                    } else {
+                        self.theme.tag()
+                    };
+                    spans.push(Span::styled(format!(" #{}", tag), style));
+                }
+                let mut item = ListItem::from(Line::from(spans)).bg(color);
+                if self.sound_manager.is_sound_playing(&row.path) {
+                    item = item.add_modifier(Modifier::BOLD).fg(self.theme.accent);
                 }
                 item
             })
@@ -258,15 +981,16 @@ impl App {
             .title(Line::raw("Sounds List").centered())
             .borders(Borders::TOP)
             .border_set(symbols::border::EMPTY)
-            .border_style(TODO_HEADER_STYLE)
-            .bg(TEAL.c800);
+            .border_style(self.theme.header())
+            .bg(self.theme.panel);
 
         let [cat_layout, list_layout] =
             Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(block.inner(area));
 
-        let selected_style = SELECTED_STYLE;
+        let selected_style = self.theme.selected();
         let symbol = " => ";
 
+        self.list_area = list_layout;
         let list = List::new(items)
             .highlight_style(selected_style)
             .highlight_symbol(symbol)
@@ -277,77 +1001,314 @@ impl App {
         StatefulWidget::render(list, list_layout, buf, &mut self.state);
     }
 
-    fn render_current_sounds(&self, area: Rect, buf: &mut Buffer) {
+    //Renders the category tabs, with the number of playing sounds of each category
+    fn render_tabs(&self, area: Rect, buf: &mut Buffer) {
+        let layers: Vec<(&str, &str)> = self
+            .layers()
+            .iter()
+            .filter_map(|l| self.sound_manager.get_sound_by_path(&l.source))
+            .map(|s| (s.path(), s.category()))
+            .collect();
+        let mut titles = vec![format!(" 0 All ({}) ", layers.len())];
+        titles.extend((0..self.category_count()).map(|i| {
+            let name = self.category_name(i);
+            let playing = layers
+                .iter()
+                .filter(|(path, category)| match self.scope(i) {
+                    Scope::Sounds(paths) => paths.iter().any(|p| p == path),
+                    Scope::Tags(filter) => tags_match(filter, self.library.tags(path)),
+                    _ => *category == name,
+                })
+                .count();
+            let key = if i < 9 { format!("{} ", i + 1) } else { String::new() };
+            if playing > 0 {
+                format!(" {}{} ({}) ", key, name.to_uppercase(), playing)
+            } else {
+                format!(" {}{} ", key, name.to_uppercase())
+            }
+        }));
+        let selected = self.category.map_or(0, |c| c + 1);
+
+        // Scroll the tabs so that the selected one is visible
+        let width = |t: &String| t.chars().count() + 1;
+        let mut first = 0;
+        while first < selected
+            && titles[first..=selected].iter().map(width).sum::<usize>() > area.width as usize
+        {
+            first += 1;
+        }
+
+        Tabs::new(titles.into_iter().skip(first))
+            .select(selected - first)
+            .style(self.theme.header())
+            .highlight_style(self.theme.selected().fg(self.theme.accent))
+            .padding("", "")
+            .divider("|")
+            .render(area, buf);
+    }
+
+    fn help_line(&self, keys: &str, description: &str) -> Line<'static> {
+        Line::from(vec![
+            Span::styled(format!("  {:<22}", keys), Style::new().fg(self.theme.accent).bold()),
+            Span::raw(description.to_string()),
+        ])
+    }
+
+    // Text with the chars matching the search underlined
+    fn highlight(&self, text: &str, matches: &[usize]) -> Vec<Span<'static>> {
+        text.chars()
+            .enumerate()
+            .map(|(i, c)| {
+                if matches.contains(&i) {
+                    Span::styled(c.to_string(), self.theme.matched())
+                } else {
+                    Span::raw(c.to_string())
+                }
+            })
+            .collect()
+    }
+
+    fn render_current_sounds(&mut self, area: Rect, buf: &mut Buffer) {
+        let title_style = if self.focus == Focus::Mixer {
+            self.theme.header().fg(self.theme.accent)
+        } else {
+            self.theme.header()
+        };
         let block = Block::new()
-            .title(Line::styled("Mixer",TODO_HEADER_STYLE).centered())
+            .title(Line::styled("Mixer", title_style).centered())
             .borders(Borders::LEFT)
             .border_set(symbols::border::PROPORTIONAL_TALL)
-            .border_style(MIXER_BORDERS_STYLE)
-            .bg(NORMAL_ROW_BG);
+            .border_style(self.theme.borders())
+            .bg(self.theme.background);
+        let inner = block.inner(area);
+        self.mixer_area = area;
 
-        let sounds = self.sound_manager.playing_sounds();
-        let mut constr: Vec<Constraint> = vec![];
-        for _i in 0..sounds.len() {
-            constr.push(Constraint::Length(1));
-            constr.push(Constraint::Length(1));
-            constr.push(Constraint::Length(1));
+        let layers = self.layers();
+        let levels = self.sound_manager.levels();
+        self.layer = self.layer.min(layers.len().saturating_sub(1));
+        if layers.is_empty() {
+            self.focus = Focus::List;
         }
-        constr.push(Constraint::Fill(1));
 
-        let layouts = Layout::vertical(constr).split(block.inner(area));
+        // Three rows per layer with the master level below when they fit, else one line per
+        // layer and the master level only if there is still room
+        let count = layers.len() as u16;
+        let compact = count * LAYER_ROWS + MASTER_ROWS > inner.height;
+        let rows = if compact { 1 } else { LAYER_ROWS };
+        let master_rows = if count * rows + MASTER_ROWS <= inner.height { MASTER_ROWS } else { 0 };
+        let [layers_area, master_area] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(master_rows)]).areas(inner);
 
         block.render(area, buf);
+        self.gauges.clear();
+        let visible = (layers_area.height / rows) as usize;
+        if layers_area.is_empty() || visible == 0 {
+            return;
+        }
 
-        sounds.iter().enumerate().for_each(|(i, (p, _))| {
-            let path = p.as_str();
-            if !self.sound_manager.is_sound_playing(path) {
-                return;
-            }
+        // Scroll so that the selected layer is visible
+        if self.layer < self.mixer_offset {
+            self.mixer_offset = self.layer;
+        } else if self.layer >= self.mixer_offset + visible {
+            self.mixer_offset = self.layer + 1 - visible;
+        }
+        self.mixer_offset = self.mixer_offset.min(layers.len().saturating_sub(visible));
+        let scrolled = layers.len() > visible;
 
-            let volume = match self.sound_manager.get_sound_by_path(path) {
-                Some(sound) => sound.volume(),
-                None => 0.0,
-            };
+        layers
+            .iter()
+            .enumerate()
+            .skip(self.mixer_offset)
+            .take(visible)
+            .for_each(|(i, layer)| {
+                let y = layers_area.y + (i - self.mixer_offset) as u16 * rows;
+                let mut row = Rect::new(layers_area.x, y, layers_area.width, rows)
+                    .intersection(layers_area);
+                if scrolled {
+                    // Room for the scrollbar
+                    row.width = row.width.saturating_sub(1);
+                }
+                let [name_area, gauge_area, meter_area] = if compact {
+                    Layout::horizontal([
+                        Constraint::Percentage(40),
+                        Constraint::Fill(1),
+                        Constraint::Length(12),
+                    ])
+                    .spacing(1)
+                    .areas(row)
+                } else {
+                    Layout::vertical([Constraint::Length(1); 3]).areas(row)
+                };
+
+                let mut text = layer.source.clone();
+                if layer.pan < -0.005 {
+                    text += &format!(" L{:.0}%", -layer.pan * 100.0);
+                } else if layer.pan > 0.005 {
+                    text += &format!(" R{:.0}%", layer.pan * 100.0);
+                }
+                if layer.muted {
+                    text += " [Muted]";
+                }
+                let mut name = Paragraph::new(text);
+                if self.focus == Focus::Mixer && i == self.layer {
+                    name = name.style(self.theme.selected());
+                }
+                name.render(name_area, buf);
 
-            Paragraph::new(path)
-                .wrap(Wrap { trim: false })
-                .render(layouts[3 * i], buf);
+                // Fixed width label so the line does not move while dragging it
+                let filled = if layer.muted { self.theme.selection } else { self.theme.text };
+                LineGauge::default()
+                    .filled_style(Style::default().fg(filled))
+                    .unfilled_style(Style::default().fg(self.theme.dim))
+                    .ratio(layer.volume.into())
+                    .label(format!("{:>3.0}%", layer.volume * 100.0))
+                    .line_set(symbols::line::THICK)
+                    .render(gauge_area, buf);
+                self.gauges.push((gauge_area, layer.source.clone()));
 
-            LineGauge::default()
-                .filled_style(Style::default().fg(TEAL.c100))
-                .unfilled_style(Style::default().fg(TEAL.c800))
-                .ratio(volume.into())
-                .line_set(symbols::line::THICK)
-                .render(layouts[3 * i + 1], buf);
-        });
+                let level = levels.get(i).copied().unwrap_or_default();
+                self.render_meter(level, meter_area, buf);
+            });
+
+        if scrolled {
+            let mut state = ScrollbarState::new(layers.len().saturating_sub(visible) + 1)
+                .position(self.mixer_offset)
+                .viewport_content_length(visible);
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(None)
+                .end_symbol(None)
+                .style(Style::default().fg(self.theme.dim))
+                .render(layers_area, buf, &mut state);
+        }
+
+        // The master level history and the waveform take the bottom of the mixer
+        if master_area.is_empty() {
+            return;
+        }
+        let master = Level::mix(&levels);
+        let title = match decibels(master.rms) {
+            Some(db) => format!("Master level {:.0} dB", db),
+            None => "Master level".to_string(),
+        };
+        let [title_area, history_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(master_area);
+        Paragraph::new(title).render(title_area, buf);
+        let [history_area, wave_area] =
+            Layout::horizontal([Constraint::Fill(2), Constraint::Fill(1)])
+                .spacing(1)
+                .areas(history_area);
+        self.render_wave(wave_area, buf);
+        let skip = self
+            .master_history
+            .len()
+            .saturating_sub(history_area.width as usize);
+        let history: Vec<u64> = self.master_history.iter().skip(skip).copied().collect();
+        Sparkline::default()
+            .data(&history)
+            .max(100)
+            .style(Style::default().fg(self.theme.tag))
+            .render(history_area, buf);
+    }
+
+    // Last samples of the mix, scaled to their peak so that quiet mixes show too
+    fn render_wave(&self, area: Rect, buf: &mut Buffer) {
+        let wave = self.sound_manager.wave();
+        let peak = wave.iter().fold(WAVE_FLOOR, |peak, s| peak.max(s.abs())) as f64;
+        Canvas::default()
+            .marker(symbols::Marker::Braille)
+            .x_bounds([0.0, wave.len().saturating_sub(1) as f64])
+            .y_bounds([-peak, peak])
+            .paint(|ctx| {
+                for (x, pair) in wave.windows(2).enumerate() {
+                    ctx.draw(&canvas::Line {
+                        x1: x as f64,
+                        y1: pair[0] as f64,
+                        x2: (x + 1) as f64,
+                        y2: pair[1] as f64,
+                        color: self.theme.accent,
+                    });
+                }
+            })
+            .render(area, buf);
+    }
+
+    // RMS level of a layer as a bar with its peak marked, after a label aligned on the gauge
+    fn render_meter(&self, level: Level, area: Rect, buf: &mut Buffer) {
+        if area.is_empty() {
+            return;
+        }
+        let label = decibels(level.rms).map_or("  --".to_string(), |db| format!("{:>4.0}", db));
+        buf.set_stringn(area.x, area.y, label, 4, Style::default().fg(self.theme.dim));
+        let width = area.width.saturating_sub(5);
+        let rms = (meter_ratio(level.rms) * width as f64).round() as u16;
+        let peak = (meter_ratio(level.peak) * width as f64).round() as u16;
+        for x in 0..width {
+            let (symbol, color) = if x < rms {
+                ("▃", self.theme.tag)
+            } else if x + 1 == peak {
+                ("▏", self.theme.accent)
+            } else {
+                continue;
+            };
+            if let Some(cell) = buf.cell_mut((area.x + 5 + x, area.y)) {
+                cell.set_symbol(symbol).set_fg(color);
+            }
+        }
     }
 }
 
 //Renders whole app
 impl Widget for &mut App {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        let footer_length = if true { 3 } else { 2 };
-        let [header_area, main_area, footer_area] = Layout::vertical([
-            Constraint::Length(2),
+        if self.compact {
+            self.render_compact(area, buf);
+            if self.help.is_some() {
+                self.render_help(area, buf);
+            }
+            return;
+        }
+        let short = area.height < SHORT_HEIGHT;
+        let [header_area, tabs_area, main_area, footer_area] = Layout::vertical([
+            Constraint::Length(if short { 1 } else { 2 }),
+            Constraint::Length(1),
             Constraint::Fill(1),
-            Constraint::Length(footer_length),
+            Constraint::Length(if short { 0 } else { 2 }),
         ])
         .areas(area);
 
-        let [list_area, item_area] =
-            Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(main_area);
+        // Side by side, or stacked in narrow terminals
+        let panels = [Constraint::Fill(1), Constraint::Fill(1)];
+        let [list_area, item_area] = if area.width < NARROW_WIDTH {
+            Layout::vertical(panels).areas(main_area)
+        } else {
+            Layout::horizontal(panels).areas(main_area)
+        };
 
         self.render_header(header_area, buf);
         self.render_footer(footer_area, buf);
+        self.render_tabs(tabs_area, buf);
         self.render_list(list_area, buf);
         self.render_current_sounds(item_area, buf);
+        if self.help.is_some() {
+            self.render_help(area, buf);
+        }
     }
 }
 
-pub const fn alternate_colors(i: usize) -> Color {
-    if i % 2 == 0 {
-        NORMAL_ROW_BG
-    } else {
-        ALT_ROW_BG_COLOR
-    }
+// Part of the area with the given percentages of its size
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let [area] = Layout::horizontal([Constraint::Percentage(width)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [area] = Layout::vertical([Constraint::Percentage(height)])
+        .flex(Flex::Center)
+        .areas(area);
+    area
+}
+
+// Volume under a column of a gauge, the line starts after the 4 characters label and a space
+fn gauge_volume(area: Rect, column: u16) -> f32 {
+    let start = area.x + 5;
+    let width = area.width.saturating_sub(5).max(1);
+    (column.saturating_sub(start) as f32 / width as f32).clamp(0.0, 1.0)
 }
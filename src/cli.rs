@@ -0,0 +1,476 @@
+use crate::app::App;
+use crate::config::{Config, ConfigError, ConfigWatch};
+use crate::control::{Command as ControlCommand, ControlRequest};
+use crate::daemon::{self, default_socket_path, DaemonClient};
+use crate::dirs::Dirs;
+use crate::export::export_wav;
+use crate::files::recover;
+use crate::http;
+use crate::import::{import_file, ImportOptions};
+use crate::keymap::Action;
+use crate::library::Library;
+use crate::osc;
+use crate::sink_handle::output_device;
+use crate::watcher;
+use crate::sound_manager::{
+    load_available_sounds, read_preset, sound_files, sound_folders, Session, SoundData, SoundManager, SoundManagerOptions,
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Desktop ambient sound player
+#[derive(Parser)]
+#[command(name = "serenit", version)]
+pub struct Cli {
+    /// Saved mix file, the other files are then kept in its folder
+    /// [default: sounds.json in the config folder]
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Folder containing the sound categories [default: sound_root of config.toml, or ./sounds]
+    #[arg(long, global = true, value_name = "DIR")]
+    pub sounds: Option<String>,
+
+    /// Starting master volume, between 0 and 1 [default: the one of the last session]
+    #[arg(long, global = true, value_name = "VOLUME", value_parser = parse_volume)]
+    pub volume: Option<f32>,
+
+    /// Control socket of the daemon [default: $XDG_RUNTIME_DIR/serenit.sock]
+    #[arg(long, global = true, value_name = "FILE")]
+    pub socket: Option<PathBuf>,
+
+    /// Serve the HTTP API, on 127.0.0.1:7777 unless an address is given
+    #[arg(long, global = true, value_name = "ADDRESS", num_args = 0..=1, default_missing_value = http::DEFAULT_ADDRESS)]
+    pub http: Option<String>,
+
+    /// Listen for OSC messages, on 127.0.0.1:9000 unless an address is given
+    #[arg(long, global = true, value_name = "ADDRESS", num_args = 0..=1, default_missing_value = osc::DEFAULT_ADDRESS)]
+    pub osc: Option<String>,
+
+    /// Start the user interface as a single status line
+    #[arg(long)]
+    pub compact: bool,
+
+    /// Print the state of the running daemon for a status bar and exit
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, default_missing_value = "text")]
+    pub status: Option<StatusFormat>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum StatusFormat {
+    /// A line of text, for i3blocks and the like
+    Text,
+    /// The json expected by the custom modules of waybar
+    Waybar,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Play a preset without the user interface (where the last session was left if none is given)
+    Play {
+        /// Preset name or path to a preset file
+        preset: Option<String>,
+        /// Stop after this many seconds
+        #[arg(long, value_name = "SECONDS")]
+        duration: Option<u64>,
+    },
+    /// List the available sounds and presets
+    List,
+    /// List the key bindings of the user interface
+    Keys,
+    /// Show where the files are kept
+    Paths,
+    /// Mix a preset into a wav file
+    Export {
+        /// Preset name or path to a preset file
+        preset: String,
+        /// Output wav file
+        output: PathBuf,
+        /// Length of the exported file
+        #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+        duration: u64,
+    },
+    /// Add sounds to the user sounds folder, e.g. `serenit import tin-roof.mp3 --category rain`
+    Import(ImportArgs),
+    /// Keep playing in the background, controlled through the socket
+    Daemon {
+        /// Preset name or path to a preset file (where the last session was left if none is given)
+        preset: Option<String>,
+        /// Start in the background and return immediately
+        #[arg(long)]
+        detach: bool,
+    },
+    /// Send a command to the daemon, e.g. `serenit send toggle_pause`
+    Send {
+        #[arg(required = true, trailing_var_arg = true)]
+        command: Vec<String>,
+    },
+}
+
+#[derive(Args)]
+pub struct ImportArgs {
+    /// Sound file, or folder whose sound files are all imported
+    path: PathBuf,
+    /// Category of the sounds [default: the name of the folder, or "imported"]
+    #[arg(long)]
+    category: Option<String>,
+    /// Name shown in the sounds list, for a single file [default: the file name]
+    #[arg(long)]
+    name: Option<String>,
+    /// Link to the files instead of copying them
+    #[arg(long, conflicts_with_all = ["start", "end", "normalize"])]
+    link: bool,
+    /// Drop what comes before this time, the sound is then written as wav
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    start: Option<f32>,
+    /// Drop what comes after this time, the sound is then written as wav
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    end: Option<f32>,
+    /// Bring the loudness to the one of the other sounds, the sound is then written as wav
+    #[arg(long)]
+    normalize: bool,
+    /// Replace a sound of the same name
+    #[arg(long)]
+    force: bool,
+}
+
+impl Cli {
+    /// Startup options from the command line, then from the config
+    pub fn options(&self, config: &Config) -> Result<SoundManagerOptions> {
+        let output_device = match &config.output_device {
+            Some(name) => Some(output_device(name).map_err(|e| {
+                ConfigError::Value(self.dirs().config_file(), e)
+            })?),
+            None => None,
+        };
+        Ok(SoundManagerOptions {
+            dirs: self.dirs(),
+            config_path: self.config.clone(),
+            sound_root: self.sound_root(config),
+            master_volume: self.volume,
+            preset: None,
+            default_preset: config.default_preset.clone(),
+            max_layers: config.max_layers,
+            volumes: config.volumes(),
+            output_device,
+            silent: false,
+            restore_pause: false,
+        })
+    }
+
+    fn sound_root(&self, config: &Config) -> String {
+        self.sounds.clone().unwrap_or_else(|| config.sound_root.clone())
+    }
+
+    fn sound_folders(&self, config: &Config) -> Vec<PathBuf> {
+        sound_folders(&self.sound_root(config), &self.dirs())
+    }
+
+    pub fn socket_path(&self) -> PathBuf {
+        self.socket.clone().unwrap_or_else(default_socket_path)
+    }
+
+    /// The XDG folders, or the one of the saved mix when `--config` is given
+    pub fn dirs(&self) -> Dirs {
+        match self.config.as_deref().and_then(Path::parent) {
+            Some(dir) if dir.as_os_str().is_empty() => Dirs::in_dir(Path::new(".")),
+            Some(dir) => Dirs::in_dir(dir),
+            None => Dirs::from_env(),
+        }
+    }
+
+    fn config_path(&self) -> PathBuf {
+        self.config.clone().unwrap_or_else(|| self.dirs().saved_mix())
+    }
+
+    /// The checked settings of `config.toml`
+    fn load_config(&self) -> Result<Config> {
+        Ok(Config::load(&self.dirs().config_file())?)
+    }
+
+    /// Lets the user recover the files that would be overwritten if they are corrupt,
+    /// false if they would rather quit
+    fn recover_files(&self) -> Result<bool> {
+        let dirs = self.dirs();
+        Ok(recover::<Vec<SoundData>>(&self.config_path())?
+            && recover::<Session>(&dirs.session())?
+            && recover::<Library>(&dirs.library())?)
+    }
+}
+
+fn parse_seconds(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(seconds) if seconds >= 0.0 => Ok(seconds),
+        _ => Err(format!("'{}' is not a number of seconds", s)),
+    }
+}
+
+fn parse_volume(s: &str) -> Result<f32, String> {
+    let volume: f32 = s.parse().map_err(|_| format!("'{}' is not a number", s))?;
+    if (0.0..=1.0).contains(&volume) {
+        Ok(volume)
+    } else {
+        Err("the volume must be between 0 and 1".to_string())
+    }
+}
+
+/// Starts the user interface, attached to the daemon if one is running
+pub fn tui(cli: &Cli) -> Result<()> {
+    let config = cli.load_config()?;
+    let config_file = cli.dirs().config_file();
+    let app = match DaemonClient::connect(&cli.socket_path()) {
+        Ok(mut client) => {
+            // The daemon owns the other files, the library is written by the interface
+            if !recover::<Library>(&cli.dirs().library())? {
+                return Ok(());
+            }
+            let mut options = cli.options(&config)?;
+            options.silent = true;
+            let mut sound_manager = SoundManager::new(options)?;
+            sound_manager.apply_status(&client.send(&ControlCommand::Status)?.status);
+            App::new(sound_manager, Some(client), &config, config_file, cli.compact)?
+        }
+        Err(_) => {
+            if !cli.recover_files()? {
+                return Ok(());
+            }
+            let mut options = cli.options(&config)?;
+            options.restore_pause = true;
+            let sound_manager = SoundManager::new(options)?;
+            let app = App::new(sound_manager, None, &config, config_file, cli.compact)?;
+            start_services(cli, &app.control_sender())?;
+            app
+        }
+    };
+    // Also when attached, the mirrored sounds list follows the folders
+    watcher::start(cli.sound_folders(&config), app.control_sender());
+    let terminal = ratatui::init();
+    let app_result = app.run(terminal);
+    ratatui::restore();
+    app_result
+}
+
+pub fn play(cli: &Cli, preset: Option<String>, duration: Option<u64>) -> Result<()> {
+    let config = cli.load_config()?;
+    if !cli.recover_files()? {
+        return Ok(());
+    }
+    let mut options = cli.options(&config)?;
+    options.preset = preset;
+    let sound_manager = start(options)?;
+    println!(
+        "Playing {} ({} sounds), Ctrl-C to stop",
+        sound_manager.current_preset().unwrap_or("saved mix"),
+        sound_manager.playing_sounds().len()
+    );
+    let start = Instant::now();
+    loop {
+        thread::sleep(Duration::from_millis(500));
+        if duration.is_some_and(|d| start.elapsed() >= Duration::from_secs(d)) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+pub fn list(cli: &Cli) -> Result<()> {
+    let config = cli.load_config()?;
+    let sounds = load_available_sounds(&cli.sound_folders(&config), &config.volumes());
+    let mut categories: Vec<&str> = vec![];
+    sounds.iter().for_each(|s| {
+        if !categories.contains(&s.category()) {
+            categories.push(s.category());
+        }
+    });
+    for category in categories {
+        println!("{}", category.to_uppercase());
+        sounds
+            .iter()
+            .filter(|s| s.category() == category)
+            .for_each(|s| println!("  {}\t{}", s.name(), s.path()));
+    }
+    println!("PRESETS");
+    cli.dirs()
+        .list_presets()
+        .iter()
+        .for_each(|p| println!("  {}", p));
+    Ok(())
+}
+
+pub fn paths(cli: &Cli) -> Result<()> {
+    let dirs = cli.dirs();
+    let paths = [
+        ("config", dirs.config_file()),
+        ("saved mix", cli.config_path()),
+        ("presets", dirs.presets()),
+        ("library", dirs.library()),
+        ("user sounds", dirs.sounds()),
+        ("cache", dirs.cache.clone()),
+        ("session", dirs.session()),
+        ("socket", cli.socket_path()),
+    ];
+    for (name, path) in paths {
+        println!("{:<12}{}", name, path.display());
+    }
+    Ok(())
+}
+
+pub fn keys(cli: &Cli) -> Result<()> {
+    let keymap = cli.load_config()?.keymap(&cli.dirs().config_file())?;
+    for context in Action::CONTEXTS {
+        println!("{}", context.to_uppercase());
+        for action in Action::ALL.iter().filter(|a| a.context() == context) {
+            let keys: Vec<String> = keymap.keys(*action).iter().map(|k| k.to_string()).collect();
+            println!("  {:<18}{:<20}{}", action.name(), keys.join(" "), action.description());
+        }
+    }
+    Ok(())
+}
+
+pub fn export(cli: &Cli, preset: &str, output: &Path, duration: u64) -> Result<()> {
+    let path = cli.dirs().preset(preset);
+    let sounds = read_preset(&path)?;
+    export_wav(&sounds, cli.volume.unwrap_or(1.0), output, Duration::from_secs(duration))?;
+    println!("Exported {} to {}", preset, output.display());
+    Ok(())
+}
+
+pub fn import(cli: &Cli, args: &ImportArgs) -> Result<()> {
+    let folder = cli.dirs().sounds();
+    let is_dir = args.path.is_dir();
+    if is_dir && args.name.is_some() {
+        return Err(eyre!("--name is for a single file, not a folder"));
+    }
+    // The sounds of a folder take its name
+    let folder_name = || fs::canonicalize(&args.path).ok()?.file_name()?.to_str().map(String::from);
+    let category = match &args.category {
+        Some(category) => category.clone(),
+        None => is_dir.then(folder_name).flatten().unwrap_or_else(|| "imported".to_string()),
+    };
+    let options = ImportOptions {
+        category,
+        name: args.name.clone(),
+        link: args.link,
+        start: args.start,
+        end: args.end,
+        normalize: args.normalize,
+        force: args.force,
+    };
+    if !is_dir {
+        let target = import_file(&folder, &args.path, &options)?;
+        println!("Imported {} to {}", args.path.display(), target.display());
+        return Ok(());
+    }
+    let files = sound_files(&args.path);
+    let mut imported = 0;
+    for file in &files {
+        match import_file(&folder, file, &options) {
+            Ok(target) => {
+                println!("Imported {} to {}", file.display(), target.display());
+                imported += 1;
+            }
+            Err(e) => eprintln!("Skipped: {}", e),
+        }
+    }
+    if imported == 0 {
+        return Err(eyre!("No sound imported from {}", args.path.display()));
+    }
+    println!("{} of {} sounds imported", imported, files.len());
+    Ok(())
+}
+
+pub fn daemon(cli: &Cli, preset: Option<String>, detach: bool) -> Result<()> {
+    // Before detaching, while the user can still answer
+    let config = cli.load_config()?;
+    let mut options = cli.options(&config)?;
+    if !cli.recover_files()? {
+        return Ok(());
+    }
+    if let Some(preset) = &preset {
+        read_preset(&cli.dirs().preset(preset))
+            .map_err(|e| eyre!("Cannot load the preset {}: {}", preset, e))?;
+    }
+    if detach {
+        return daemon::detach();
+    }
+    options.preset = preset;
+    let (tx, rx) = mpsc::channel();
+    start_services(cli, &tx)?;
+    watcher::start(cli.sound_folders(&config), tx.clone());
+    let config = ConfigWatch::new(cli.dirs().config_file(), &config);
+    daemon::run(start(options)?, config, &cli.socket_path(), tx, rx)
+}
+
+// Sound manager playing the preset asked for, or the last session
+fn start(options: SoundManagerOptions) -> Result<SoundManager> {
+    let preset = options.preset.clone();
+    SoundManager::new(options).map_err(|e| match preset {
+        Some(preset) => eyre!("Cannot load the preset {}: {}", preset, e),
+        None => e.into(),
+    })
+}
+
+/// Starts the interfaces served by whoever owns the sound manager
+fn start_services(cli: &Cli, tx: &Sender<ControlRequest>) -> Result<()> {
+    #[cfg(feature = "mpris")]
+    crate::mpris::start(tx.clone());
+    if let Some(address) = &cli.http {
+        http::start(address, tx.clone())?;
+    }
+    if let Some(address) = &cli.osc {
+        osc::start(address, tx.clone())?;
+    }
+    Ok(())
+}
+
+/// One line for status bars, empty when no daemon is running
+pub fn status(cli: &Cli, format: StatusFormat) -> Result<()> {
+    let status = match DaemonClient::connect(&cli.socket_path()) {
+        Ok(mut client) => Some(client.send(&ControlCommand::Status)?.status),
+        Err(_) => None,
+    };
+    match format {
+        StatusFormat::Text => println!("{}", status.map(|s| s.summary()).unwrap_or_default()),
+        StatusFormat::Waybar => {
+            let json = match status {
+                Some(status) => {
+                    let tooltip: Vec<String> = status
+                        .layers
+                        .iter()
+                        .map(|l| format!("{} {:.0}%", l.source, l.volume * 100.0))
+                        .collect();
+                    serde_json::json!({
+                        "text": status.summary(),
+                        "tooltip": tooltip.join("\n"),
+                        "class": if status.paused { "paused" } else { "playing" },
+                        "percentage": (status.master_volume * 100.0).round() as u32,
+                    })
+                }
+                None => serde_json::json!({ "text": "", "class": "stopped" }),
+            };
+            println!("{}", json);
+        }
+    }
+    Ok(())
+}
+
+pub fn send(cli: &Cli, command: &[String]) -> Result<()> {
+    let socket = cli.socket_path();
+    let mut client = DaemonClient::connect(&socket)
+        .map_err(|_| color_eyre::eyre::eyre!("no daemon running on {}", socket.display()))?;
+    let response = client.send_line(&command.join(" "))?;
+    println!("{}", serde_json::to_string(&response)?);
+    if let Some(error) = response.error {
+        return Err(color_eyre::eyre::eyre!(error));
+    }
+    Ok(())
+}
@@ -0,0 +1,222 @@
+use crate::keymap::{Keymap, KeymapError};
+use crate::sound_manager::{Volumes, DEFAULT_MAX_LAYERS, DEFAULT_SOUND_ROOT, MAX_LAYERS};
+use crate::theme::{no_color, Theme, ThemeConfig, NO_COLOR_THEME};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Cannot read {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("Invalid config {0}: {1}")]
+    Parse(PathBuf, toml::de::Error),
+    #[error("Invalid config {0}: {1}")]
+    Keys(PathBuf, KeymapError),
+    #[error("Invalid config {0}: {1}")]
+    Theme(PathBuf, String),
+    #[error("Invalid config {0}: {1}")]
+    Value(PathBuf, String),
+}
+
+/// User settings from `config.toml`, in the config folder
+///
+/// ```toml
+/// theme = "high-contrast"
+/// max_layers = 12
+/// volume_step = 0.05
+///
+/// [volumes]
+/// default = 0.4
+/// noise = 0.1
+///
+/// [keys]
+/// volume_down = ["Left", "h"]
+/// master_up = ["Ctrl+Right", "+"]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Most sounds played together
+    pub max_layers: usize,
+    /// Volume change of a key press
+    pub volume_step: f32,
+    /// Folder containing the sound categories, `--sounds` takes precedence
+    pub sound_root: String,
+    /// Starting volume of the sounds by category or sound name, `default` for the others
+    pub volumes: HashMap<String, f32>,
+    /// Preset played when there is no session to restore, before the saved mix
+    pub default_preset: Option<String>,
+    /// Name of the audio output, the system default when None
+    pub output_device: Option<String>,
+    /// Action name to the keys triggering it, replaces the default keys of that action
+    pub keys: HashMap<String, Vec<String>>,
+    /// Name of a built-in or user theme, teal by default
+    pub theme: Option<String>,
+    /// User themes, by name
+    pub themes: HashMap<String, ThemeConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            max_layers: DEFAULT_MAX_LAYERS,
+            volume_step: 0.02,
+            sound_root: DEFAULT_SOUND_ROOT.to_string(),
+            volumes: HashMap::new(),
+            default_preset: None,
+            output_device: None,
+            keys: HashMap::new(),
+            theme: None,
+            themes: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads and checks the config, a missing file gives the defaults
+    pub fn load(path: &Path) -> Result<Config, ConfigError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(ConfigError::Io(path.to_path_buf(), e)),
+        };
+        let config: Config =
+            toml::from_str(&text).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))?;
+        config.check(path)?;
+        Ok(config)
+    }
+
+    // The values toml cannot check, the keys and the theme included
+    fn check(&self, path: &Path) -> Result<(), ConfigError> {
+        let error = |message: String| Err(ConfigError::Value(path.to_path_buf(), message));
+        if !(1..=MAX_LAYERS).contains(&self.max_layers) {
+            return error(format!(
+                "max_layers must be between 1 and {}, not {}",
+                MAX_LAYERS, self.max_layers
+            ));
+        }
+        if !(self.volume_step > 0.0 && self.volume_step <= 0.5) {
+            return error(format!(
+                "volume_step must be above 0 and at most 0.5, not {}",
+                self.volume_step
+            ));
+        }
+        if self.sound_root.is_empty() {
+            return error("sound_root cannot be empty".to_string());
+        }
+        let mut volumes: Vec<_> = self.volumes.iter().collect();
+        volumes.sort_by(|a, b| a.0.cmp(b.0));
+        if let Some((name, volume)) = volumes.iter().find(|(_, v)| !(0.0..=1.0).contains(*v)) {
+            return error(format!(
+                "the volume of '{}' must be between 0 and 1, not {}",
+                name, volume
+            ));
+        }
+        if self.default_preset.as_deref().is_some_and(str::is_empty) {
+            return error("default_preset cannot be empty".to_string());
+        }
+        self.keymap(path)?;
+        self.theme(path)?;
+        Ok(())
+    }
+
+    /// The built-in starting volumes with the ones of the `[volumes]` table
+    pub fn volumes(&self) -> Volumes {
+        let mut volumes = Volumes::default();
+        volumes.extend(&self.volumes);
+        volumes
+    }
+
+    /// Default bindings with the ones of the `[keys]` table
+    pub fn keymap(&self, path: &Path) -> Result<Keymap, ConfigError> {
+        Keymap::new(&self.keys).map_err(|e| ConfigError::Keys(path.to_path_buf(), e))
+    }
+
+    /// The chosen theme, a user theme before a built-in one of the same name,
+    /// no colours at all when `NO_COLOR` is set
+    pub fn theme(&self, path: &Path) -> Result<Theme, ConfigError> {
+        let error = |message: String| ConfigError::Theme(path.to_path_buf(), message);
+        let name = self.theme.as_deref().unwrap_or("teal");
+        // Checked even when the colours are off
+        let theme = match self.themes.get(name) {
+            Some(theme) => theme.theme().ok_or_else(|| {
+                error(format!(
+                    "unknown base '{}' of the theme '{}'",
+                    theme.base.as_deref().unwrap_or_default(),
+                    name
+                ))
+            }),
+            None => Theme::builtin(name).ok_or_else(|| error(format!("unknown theme '{}'", name))),
+        }?;
+        Ok(if no_color() { NO_COLOR_THEME } else { theme })
+    }
+}
+
+/// `config.toml` read again when it changes, by the interface and the daemon
+pub struct ConfigWatch {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    // The settings in use for the ones that cannot change without a restart
+    started: Config,
+}
+
+/// A config read again, the keys, theme, volume step and volumes apply at once
+pub struct Reloaded {
+    pub config: Config,
+    /// Settings only read on start that changed since
+    pub restart: Vec<&'static str>,
+}
+
+impl ConfigWatch {
+    pub fn new(path: PathBuf, config: &Config) -> Self {
+        ConfigWatch {
+            modified: modified(&path),
+            path,
+            started: config.clone(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The config when the file changed since the last call, an invalid one being an error
+    pub fn reload(&mut self) -> Option<Result<Reloaded, ConfigError>> {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(Config::load(&self.path).map(|config| {
+            let started = &self.started;
+            let restart = [
+                ("max_layers", config.max_layers != started.max_layers),
+                ("sound_root", config.sound_root != started.sound_root),
+                ("default_preset", config.default_preset != started.default_preset),
+                ("output_device", config.output_device != started.output_device),
+            ]
+            .into_iter()
+            .filter_map(|(name, changed)| changed.then_some(name))
+            .collect();
+            Reloaded { config, restart }
+        }))
+    }
+}
+
+impl Reloaded {
+    pub fn message(&self) -> String {
+        if self.restart.is_empty() {
+            "Config reloaded".to_string()
+        } else {
+            format!("Config reloaded, restart to apply {}", self.restart.join(", "))
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,187 @@
+use crate::meter::Level;
+use crate::sound_manager::SoundData;
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{self, Sender};
+
+/// Operations that can be requested from outside the user interface
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Command {
+    /// `path` may also be the name of the sound, with or without extension
+    Toggle { path: String },
+    Volume { path: String, volume: f32 },
+    Mute { path: String, muted: bool },
+    /// -1 is left, 1 right
+    Pan { path: String, pan: f32 },
+    Master { volume: f32 },
+    Load { preset: String },
+    Pause,
+    Play,
+    TogglePause,
+    /// Saves the mix, or a named preset when given
+    Save {
+        #[serde(default)]
+        preset: Option<String>,
+    },
+    Status,
+    Sounds,
+    Categories,
+    Presets,
+    /// Reads the sound folders again, answers the playing sounds that were removed
+    Rescan,
+    Quit,
+}
+
+impl Command {
+    /// Whether the command changes what is played
+    pub fn changes_mix(&self) -> bool {
+        matches!(
+            self,
+            Command::Toggle { .. }
+                | Command::Volume { .. }
+                | Command::Mute { .. }
+                | Command::Pan { .. }
+                | Command::Master { .. }
+                | Command::Load { .. }
+                | Command::Pause
+                | Command::Play
+                | Command::TogglePause
+        )
+    }
+}
+
+/// Snapshot of the mix, sent back after every command
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Status {
+    pub paused: bool,
+    pub master_volume: f32,
+    pub preset: Option<String>,
+    /// Playing sounds, in sink order
+    pub layers: Vec<SoundData>,
+    /// Levels of the layers, in the same order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub levels: Vec<Level>,
+    /// Last samples of the mix, for the waveform, empty when nothing plays
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub wave: Vec<f32>,
+    /// Seconds the mix has been playing, pauses excluded
+    #[serde(default)]
+    pub play_time: u64,
+}
+
+impl Status {
+    /// One line summary for the compact view and the status bars
+    pub fn summary(&self) -> String {
+        let time = self.play_time;
+        format!(
+            "{} {} | {:.0}% | {}:{:02}:{:02} | {} layer{}",
+            if self.paused { "⏸" } else { "▶" },
+            self.preset.as_deref().unwrap_or("saved mix"),
+            self.master_volume * 100.0,
+            time / 3600,
+            time / 60 % 60,
+            time % 60,
+            self.layers.len(),
+            if self.layers.len() == 1 { "" } else { "s" },
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub status: Status,
+    /// Answer to the listing commands (sounds, categories, presets)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+/// A command waiting to be executed by the thread owning the `SoundManager`
+pub struct ControlRequest {
+    pub command: Command,
+    pub reply: Sender<Response>,
+}
+
+impl Command {
+    /// Parses either a json object or a plain text command such as `volume <path> 0.5`
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let line = line.trim();
+        if line.starts_with('{') {
+            return serde_json::from_str(line).map_err(|e| e.to_string());
+        }
+        let (name, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+        let command = match name {
+            "toggle" if !args.is_empty() => Command::Toggle {
+                path: args.to_string(),
+            },
+            "volume" => {
+                let (path, volume) = args
+                    .rsplit_once(' ')
+                    .ok_or("usage: volume <path> <0-1>")?;
+                Command::Volume {
+                    path: path.trim().to_string(),
+                    volume: parse_volume(volume)?,
+                }
+            }
+            "mute" | "unmute" if !args.is_empty() => Command::Mute {
+                path: args.to_string(),
+                muted: name == "mute",
+            },
+            "pan" => {
+                let (path, pan) = args.rsplit_once(' ').ok_or("usage: pan <path> <-1-1>")?;
+                Command::Pan {
+                    path: path.trim().to_string(),
+                    pan: pan
+                        .trim()
+                        .parse::<f32>()
+                        .map(|p| p.clamp(-1.0, 1.0))
+                        .map_err(|_| format!("'{}' is not a pan", pan))?,
+                }
+            }
+            "master" => Command::Master {
+                volume: parse_volume(args)?,
+            },
+            "load" if !args.is_empty() => Command::Load {
+                preset: args.to_string(),
+            },
+            "pause" => Command::Pause,
+            "play" => Command::Play,
+            "toggle_pause" => Command::TogglePause,
+            "save" => Command::Save {
+                preset: (!args.is_empty()).then(|| args.to_string()),
+            },
+            "status" => Command::Status,
+            "sounds" => Command::Sounds,
+            "categories" => Command::Categories,
+            "presets" => Command::Presets,
+            "rescan" => Command::Rescan,
+            "quit" => Command::Quit,
+            _ => return Err(format!("Unknown command: {}", line)),
+        };
+        Ok(command)
+    }
+}
+
+fn parse_volume(s: &str) -> Result<f32, String> {
+    s.trim()
+        .parse::<f32>()
+        .map(|v| v.clamp(0.0, 1.0))
+        .map_err(|_| format!("'{}' is not a volume", s))
+}
+
+/// Hands a command to the thread owning the `SoundManager` and waits for the answer,
+/// None if that thread is gone
+pub fn request(tx: &Sender<ControlRequest>, command: Command) -> Option<Response> {
+    let (reply, response) = mpsc::channel();
+    tx.send(ControlRequest { command, reply }).ok()?;
+    response.recv().ok()
+}
+
+/// Same as `request` without waiting for the answer
+pub fn send(tx: &Sender<ControlRequest>, command: Command) {
+    let (reply, _) = mpsc::channel();
+    let _ = tx.send(ControlRequest { command, reply });
+}
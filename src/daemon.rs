@@ -0,0 +1,188 @@
+//! Background player controlled through a Unix domain socket.
+//!
+//! The protocol is line based: every request is one line, either a plain text
+//! command or a json object, and every answer is one json line.
+//!
+//! ```text
+//! toggle <path>           {"cmd":"toggle","path":"./sounds/rain/light-rain.mp3"}
+//! volume <path> <0-1>     {"cmd":"volume","path":"...","volume":0.4}
+//! mute | unmute <path>    {"cmd":"mute","path":"...","muted":true}
+//! pan <path> <-1-1>       {"cmd":"pan","path":"...","pan":-0.5}
+//! master <0-1>            {"cmd":"master","volume":0.8}
+//! load <preset>           {"cmd":"load","preset":"rainy"}
+//! save [preset]           {"cmd":"save","preset":"evening"}
+//! pause | play | toggle_pause | status | quit
+//! sounds | categories | presets | rescan
+//! ```
+//!
+//! The json objects are the serialized `control::Command`.
+//!
+//! Answers look like `{"ok":true,"status":{"paused":false,"master_volume":1.0,"preset":null,"layers":[...]}}`,
+//! with an `error` field when `ok` is false.
+
+use crate::config::ConfigWatch;
+use crate::control::{request, Command, ControlRequest, Response};
+use crate::sound_manager::SoundManager;
+use cli_log::*;
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+use thiserror::Error;
+
+// How often the session is checked for autosave while no command comes
+const AUTOSAVE_TICK: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Error)]
+pub enum DaemonError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+    #[error("The daemon closed the connection")]
+    Disconnected,
+}
+
+pub fn default_socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("serenit.sock"),
+        None => std::env::temp_dir().join(format!(
+            "serenit-{}.sock",
+            std::env::var("USER").unwrap_or_default()
+        )),
+    }
+}
+
+/// Owns the sound manager and executes the commands received on the socket,
+/// or through `tx` by the other interfaces, until `quit`
+pub fn run(
+    mut sound_manager: SoundManager,
+    mut config: ConfigWatch,
+    socket: &Path,
+    tx: Sender<ControlRequest>,
+    rx: Receiver<ControlRequest>,
+) -> Result<()> {
+    if UnixStream::connect(socket).is_ok() {
+        return Err(eyre!("A daemon is already listening on {}", socket.display()));
+    }
+    // Left over by a daemon that did not exit cleanly
+    let _ = std::fs::remove_file(socket);
+    let listener = UnixListener::bind(socket)?;
+    info!("Daemon listening on {}", socket.display());
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                if let Err(e) = serve_client(stream, tx) {
+                    warn!("Client error: {}", e);
+                }
+            });
+        }
+    });
+
+    loop {
+        match rx.recv_timeout(AUTOSAVE_TICK) {
+            Ok(request) => {
+                let response = sound_manager.execute(&request.command);
+                let _ = request.reply.send(response);
+                if let Command::Quit = request.command {
+                    break;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        sound_manager.autosave();
+        match config.reload() {
+            Some(Ok(reloaded)) => {
+                sound_manager.set_volumes(reloaded.config.volumes());
+                info!("{}", reloaded.message());
+            }
+            Some(Err(e)) => warn!("{}, keeping the current settings", e),
+            None => {}
+        }
+    }
+    sound_manager.save_session();
+    let _ = std::fs::remove_file(socket);
+    Ok(())
+}
+
+/// Starts a daemon in the background with the same arguments, minus `--detach`
+pub fn detach() -> Result<()> {
+    let args: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| arg != "--detach")
+        .collect();
+    std::process::Command::new(std::env::current_exe()?)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // Own process group, so closing the terminal does not stop it
+        .process_group(0)
+        .spawn()?;
+    Ok(())
+}
+
+fn serve_client(stream: UnixStream, tx: Sender<ControlRequest>) -> Result<(), DaemonError> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match Command::parse(&line) {
+            Ok(command) => request(&tx, command).ok_or(DaemonError::Disconnected)?,
+            Err(error) => {
+                let mut response = request(&tx, Command::Status).ok_or(DaemonError::Disconnected)?;
+                response.ok = false;
+                response.error = Some(error);
+                response
+            }
+        };
+        writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+    }
+    Ok(())
+}
+
+/// Connection to a running daemon
+pub struct DaemonClient {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl DaemonClient {
+    pub fn connect(socket: &Path) -> Result<Self, DaemonError> {
+        let stream = UnixStream::connect(socket)?;
+        Ok(DaemonClient {
+            writer: stream.try_clone()?,
+            reader: BufReader::new(stream),
+        })
+    }
+
+    pub fn send(&mut self, command: &Command) -> Result<Response, DaemonError> {
+        writeln!(self.writer, "{}", serde_json::to_string(command)?)?;
+        self.read_response()
+    }
+
+    /// Sends a line as typed by the user, the daemon parses it
+    pub fn send_line(&mut self, line: &str) -> Result<Response, DaemonError> {
+        writeln!(self.writer, "{}", line.trim())?;
+        self.read_response()
+    }
+
+    fn read_response(&mut self) -> Result<Response, DaemonError> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(DaemonError::Disconnected);
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
@@ -0,0 +1,141 @@
+use cli_log::*;
+use homedir::my_home;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+const APP_DIR: &str = "serenIT";
+
+/// Where serenIT keeps its files, following the XDG base directories,
+/// or all in `$SERENIT_HOME` for portable installs
+#[derive(Debug, Clone)]
+pub struct Dirs {
+    /// Settings, the saved mix and the presets
+    pub config: PathBuf,
+    /// The library and the user sounds
+    pub data: PathBuf,
+    /// What can be computed again (sound analysis...)
+    pub cache: PathBuf,
+    /// The session
+    pub state: PathBuf,
+}
+
+impl Dirs {
+    pub fn from_env() -> Dirs {
+        let var = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty());
+        if let Some(home) = var("SERENIT_HOME") {
+            let home = PathBuf::from(home);
+            return Dirs {
+                config: home.join("config"),
+                data: home.join("data"),
+                cache: home.join("cache"),
+                state: home.join("state"),
+            };
+        }
+        let home = my_home().ok().flatten().unwrap_or_else(|| PathBuf::from("."));
+        // Relative paths are invalid and must be ignored
+        let base = |name: &str, default: &str| {
+            var(name)
+                .map(PathBuf::from)
+                .filter(|p| p.is_absolute())
+                .unwrap_or_else(|| home.join(default))
+                .join(APP_DIR)
+        };
+        Dirs {
+            config: base("XDG_CONFIG_HOME", ".config"),
+            data: base("XDG_DATA_HOME", ".local/share"),
+            cache: base("XDG_CACHE_HOME", ".cache"),
+            state: base("XDG_STATE_HOME", ".local/state"),
+        }
+    }
+
+    /// Everything in one folder, the one of the saved mix given with `--config`
+    pub fn in_dir(dir: &Path) -> Dirs {
+        Dirs {
+            config: dir.to_path_buf(),
+            data: dir.to_path_buf(),
+            cache: dir.to_path_buf(),
+            state: dir.to_path_buf(),
+        }
+    }
+
+    /// The mix saved with 's', played when there is no session
+    pub fn saved_mix(&self) -> PathBuf {
+        self.config.join("sounds.json")
+    }
+
+    pub fn config_file(&self) -> PathBuf {
+        self.config.join("config.toml")
+    }
+
+    pub fn presets(&self) -> PathBuf {
+        self.config.join("presets")
+    }
+
+    pub fn library(&self) -> PathBuf {
+        self.data.join("library.json")
+    }
+
+    /// Sounds added by the user, a folder per category
+    pub fn sounds(&self) -> PathBuf {
+        self.data.join("sounds")
+    }
+
+    pub fn session(&self) -> PathBuf {
+        self.state.join("session.json")
+    }
+
+    /// A preset is either a path to a json file or the name of a file in the presets folder
+    pub fn preset(&self, preset: &str) -> PathBuf {
+        let path = Path::new(preset);
+        if path.extension().is_some_and(|ext| ext == "json") || path.is_file() {
+            return path.to_path_buf();
+        }
+        let mut name = OsString::from(preset);
+        name.push(".json");
+        self.presets().join(name)
+    }
+
+    /// A preset of the presets folder given by its name alone, as the remote commands
+    /// must not reach other files
+    pub fn named_preset(&self, name: &str) -> Result<PathBuf, String> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\', '\0']) {
+            return Err(format!("'{}' is not a preset name", name));
+        }
+        Ok(self.presets().join(format!("{}.json", name)))
+    }
+
+    /// Moves the files older versions kept with the saved mix to their new folder
+    pub fn migrate(&self) {
+        for (old, new) in [
+            (self.config.join("library.json"), self.library()),
+            (self.config.join("session.json"), self.session()),
+        ] {
+            if old == new || !old.exists() || new.exists() {
+                continue;
+            }
+            let res = new
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::rename(&old, &new));
+            match res {
+                Ok(()) => info!("Moved {} to {}", old.display(), new.display()),
+                Err(e) => warn!("Cannot move {} to {}: {}", old.display(), new.display(), e),
+            }
+        }
+    }
+
+    /// Names of the presets, sorted
+    pub fn list_presets(&self) -> Vec<String> {
+        let mut presets: Vec<String> = match std::fs::read_dir(self.presets()) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                .filter_map(|path| path.file_stem().map(|s| s.to_string_lossy().to_string()))
+                .collect(),
+            Err(_) => vec![],
+        };
+        presets.sort();
+        presets
+    }
+}
@@ -0,0 +1,65 @@
+use crate::pan::{Pan, Panned};
+use crate::sound_manager::SoundData;
+use rodio::source::{Source, UniformSourceIterator};
+use rodio::Decoder;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+
+const SAMPLE_RATE: u32 = 44100;
+const CHANNELS: u16 = 2;
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Could not decode {0}: {1}")]
+    DecoderError(String, rodio::decoder::DecoderError),
+    #[error("Wav error: {0}")]
+    WavError(#[from] hound::Error),
+    #[error("The preset is empty")]
+    EmptyPreset,
+}
+
+/// Mixes the sounds of a preset into a 16 bits stereo wav file
+pub fn export_wav(
+    sounds: &[SoundData],
+    master_volume: f32,
+    output: &Path,
+    duration: Duration,
+) -> Result<(), ExportError> {
+    let mut layers: Vec<Box<dyn Iterator<Item = f32>>> = vec![];
+    for s in sounds.iter().filter(|s| !s.source.is_empty() && !s.muted) {
+        let file = BufReader::new(File::open(&s.source)?);
+        let decoder =
+            Decoder::new(file).map_err(|e| ExportError::DecoderError(s.source.clone(), e))?;
+        let source = decoder
+            .repeat_infinite()
+            .convert_samples::<f32>()
+            .amplify(s.volume * master_volume);
+        layers.push(Box::new(Panned::new(
+            UniformSourceIterator::new(source, CHANNELS, SAMPLE_RATE),
+            Pan::new(s.pan),
+        )));
+    }
+    if layers.is_empty() {
+        return Err(ExportError::EmptyPreset);
+    }
+
+    let spec = hound::WavSpec {
+        channels: CHANNELS,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(output, spec)?;
+    let samples = (duration.as_secs_f64() * (SAMPLE_RATE as f64) * (CHANNELS as f64)) as u64;
+    for _ in 0..samples {
+        let mixed: f32 = layers.iter_mut().filter_map(|layer| layer.next()).sum();
+        writer.write_sample((mixed.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use serde::de::{DeserializeOwned, IgnoredAny};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// Replaces a json file without ever leaving it half written: the contents go to a
+/// temporary file renamed over it, the previous version being kept as `<file>.bak`
+/// when it was valid
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let parent = path.parent().filter(|p| !p.as_os_str().is_empty());
+    if let Some(parent) = parent {
+        fs::create_dir_all(parent)?;
+    }
+    // Per process, so that two instances writing the same file do not mix their contents
+    let tmp = with_suffix(path, &format!(".{}.tmp", std::process::id()));
+    let written = File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|_| backup(path, contents))
+        .and_then(|_| fs::rename(&tmp, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+        return written;
+    }
+    // The rename is only on disk once the folder is
+    File::open(parent.unwrap_or(Path::new(".")))?.sync_all()
+}
+
+// Keeps the current version as the backup, unless it is corrupt or the same as the new
+// one, so that the backup stays the last good version that differs
+fn backup(path: &Path, contents: &[u8]) -> io::Result<()> {
+    match fs::read(path) {
+        Ok(current) if current != contents && is_json(&current) => {
+            fs::write(backup_path(path), current)
+        }
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+fn is_json(contents: &[u8]) -> bool {
+    serde_json::from_slice::<IgnoredAny>(contents).is_ok()
+}
+
+pub fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+/// Where a corrupt file is set aside
+pub fn corrupt_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".corrupt")
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Why a file cannot be read as `T`, None when it is fine or does not exist
+pub fn check<T: DeserializeOwned>(path: &Path) -> Option<String> {
+    match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str::<T>(&text).err().map(|e| e.to_string()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => Some(e.to_string()),
+    }
+}
+
+/// What to do with a corrupt file
+#[derive(Debug, PartialEq)]
+enum Recovery {
+    /// Put the backup back in place
+    Restore,
+    /// Move the file out of the way and start without it
+    SetAside,
+    Quit,
+}
+
+/// Asks what to do with a corrupt file, it is set aside when nobody can answer
+fn ask_recovery(path: &Path, error: &str, has_backup: bool) -> io::Result<Recovery> {
+    eprintln!("{} is corrupt: {}", path.display(), error);
+    if !io::stdin().is_terminal() {
+        return Ok(Recovery::SetAside);
+    }
+    let question = if has_backup {
+        "[r]estore the backup, [s]tart without it or [q]uit?"
+    } else {
+        "No backup to restore, [s]tart without it or [q]uit?"
+    };
+    let mut answer = String::new();
+    loop {
+        eprint!("{} ", question);
+        answer.clear();
+        if io::stdin().lock().read_line(&mut answer)? == 0 {
+            return Ok(Recovery::Quit);
+        }
+        match answer.trim() {
+            "r" if has_backup => return Ok(Recovery::Restore),
+            "s" => return Ok(Recovery::SetAside),
+            "q" => return Ok(Recovery::Quit),
+            _ => {}
+        }
+    }
+}
+
+/// Checks a file before starting and lets the user recover it if it is corrupt,
+/// false if they would rather quit
+pub fn recover<T: DeserializeOwned>(path: &Path) -> io::Result<bool> {
+    let Some(error) = check::<T>(path) else {
+        return Ok(true);
+    };
+    let backup = backup_path(path);
+    let has_backup = backup.exists() && check::<T>(&backup).is_none();
+    // Kept in every case, the user may want to repair it by hand
+    let corrupt = corrupt_path(path);
+    match ask_recovery(path, &error, has_backup)? {
+        Recovery::Restore => {
+            fs::rename(path, &corrupt)?;
+            fs::copy(&backup, path)?;
+            eprintln!("Restored {}", backup.display());
+        }
+        Recovery::SetAside => {
+            fs::rename(path, &corrupt)?;
+            eprintln!("Moved to {}", corrupt.display());
+        }
+        Recovery::Quit => return Ok(false),
+    }
+    Ok(true)
+}
@@ -0,0 +1,39 @@
+/// Matches the characters of the pattern in order, case insensitive,
+/// gives a score (higher is better) and the matched char positions of the text
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<(i32, Vec<usize>)> {
+    let pattern: Vec<char> = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(|c| c.to_lowercase())
+        .collect();
+    if pattern.is_empty() {
+        return Some((0, vec![]));
+    }
+    let text: Vec<char> = text.chars().collect();
+    let mut positions = Vec::with_capacity(pattern.len());
+    let mut score = 0;
+    let mut p = 0;
+    for (i, c) in text.iter().enumerate() {
+        if p == pattern.len() {
+            break;
+        }
+        if c.to_lowercase().eq(pattern[p].to_lowercase()) {
+            // Consecutive characters and word starts make better matches
+            if positions.last().is_some_and(|last| last + 1 == i) {
+                score += 5;
+            }
+            if i == 0 || !text[i - 1].is_alphanumeric() {
+                score += 3;
+            }
+            score += 1;
+            positions.push(i);
+            p += 1;
+        }
+    }
+    if p < pattern.len() {
+        return None;
+    }
+    // Prefer the shortest texts and the earliest matches
+    score -= positions[0] as i32 + text.len() as i32 / 8;
+    Some((score, positions))
+}
@@ -0,0 +1,105 @@
+use crate::control::{Command, Status};
+use std::time::{Duration, Instant};
+
+// Changes kept for undo
+const MAX_CHANGES: usize = 100;
+// Repeated changes of the same thing closer than this are undone at once (volume steps...)
+const MERGE_TIME: Duration = Duration::from_secs(1);
+
+/// A change of the mix and the mix before it
+pub struct Change {
+    /// What changed, e.g. "volume of rain"
+    pub label: String,
+    pub before: Status,
+    at: Instant,
+}
+
+/// Undo and redo stacks of the mix changes
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+impl History {
+    /// Records the mix before a change, forgetting the undone changes
+    pub fn record(&mut self, label: String, before: Status) {
+        self.redo.clear();
+        if let Some(last) = self.undo.last_mut() {
+            if last.label == label && last.at.elapsed() < MERGE_TIME {
+                last.at = Instant::now();
+                return;
+            }
+        }
+        if self.undo.len() == MAX_CHANGES {
+            self.undo.remove(0);
+        }
+        self.undo.push(Change {
+            label,
+            before,
+            at: Instant::now(),
+        });
+    }
+
+    /// The last change, `current` being kept to redo it
+    pub fn undo(&mut self, current: Status) -> Option<Change> {
+        let change = self.undo.pop()?;
+        self.redo.push(Change {
+            label: change.label.clone(),
+            before: current,
+            at: Instant::now(),
+        });
+        Some(change)
+    }
+
+    /// The last undone change, `current` being kept to undo it again
+    pub fn redo(&mut self, current: Status) -> Option<Change> {
+        let change = self.redo.pop()?;
+        self.undo.push(Change {
+            label: change.label.clone(),
+            before: current,
+            at: Instant::now(),
+        });
+        Some(change)
+    }
+}
+
+/// Commands turning the `current` mix into the `target` one
+pub fn restore_commands(current: &Status, target: &Status) -> Vec<Command> {
+    let mut commands = vec![];
+    let playing = |status: &Status, path: &str| status.layers.iter().any(|l| l.source == path);
+    // Remove first so that the added layers find a free slot
+    for layer in current.layers.iter().filter(|l| !playing(target, &l.source)) {
+        commands.push(Command::Toggle {
+            path: layer.source.clone(),
+        });
+    }
+    for layer in &target.layers {
+        let path = layer.source.clone();
+        match current.layers.iter().find(|l| l.source == layer.source) {
+            Some(now) => {
+                if now.volume != layer.volume {
+                    commands.push(Command::Volume { path: path.clone(), volume: layer.volume });
+                }
+                if now.muted != layer.muted {
+                    commands.push(Command::Mute { path: path.clone(), muted: layer.muted });
+                }
+                if now.pan != layer.pan {
+                    commands.push(Command::Pan { path, pan: layer.pan });
+                }
+            }
+            None => {
+                commands.push(Command::Toggle { path: path.clone() });
+                commands.push(Command::Volume { path: path.clone(), volume: layer.volume });
+                commands.push(Command::Mute { path: path.clone(), muted: layer.muted });
+                commands.push(Command::Pan { path, pan: layer.pan });
+            }
+        }
+    }
+    if current.master_volume != target.master_volume {
+        commands.push(Command::Master {
+            volume: target.master_volume,
+        });
+    }
+    commands
+}
@@ -0,0 +1,108 @@
+//! Small HTTP/JSON API, every route maps onto a control command.
+//!
+//! ```text
+//! GET  /api/state                  current mix
+//! GET  /api/sounds                 sounds with their volume and whether they play
+//! GET  /api/categories
+//! GET  /api/presets
+//! POST /api/toggle                 {"path": "./sounds/rain/light-rain.mp3"}
+//! POST /api/volume                 {"path": "...", "volume": 0.4}
+//! POST /api/mute                   {"path": "...", "muted": true}
+//! POST /api/pan                    {"path": "...", "pan": -0.5}
+//! POST /api/master                 {"volume": 0.8}
+//! POST /api/pause, /api/play, /api/toggle_pause, /api/save, /api/rescan
+//! POST /api/presets/<name>/load
+//! POST /api/presets/<name>/save
+//! ```
+//!
+//! Answers have the same shape as the daemon ones: `{"ok":..,"status":{..},"data":..}`.
+
+use crate::control::{self, Command, ControlRequest};
+use cli_log::*;
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use serde_json::Value;
+use std::io::Read;
+use std::sync::mpsc::Sender;
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7777";
+// Largest request body read, the commands are far smaller
+const MAX_BODY: u64 = 64 * 1024;
+
+/// Serves the API in the background
+pub fn start(address: &str, tx: Sender<ControlRequest>) -> Result<()> {
+    let server = Server::http(address).map_err(|e| eyre!("Cannot listen on {}: {}", address, e))?;
+    info!("HTTP API listening on {}", address);
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            if let Err(e) = handle(request, &tx) {
+                warn!("HTTP error: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn handle(mut request: Request, tx: &Sender<ControlRequest>) -> std::io::Result<()> {
+    let mut body = String::new();
+    request.as_reader().take(MAX_BODY + 1).read_to_string(&mut body)?;
+    if body.len() as u64 > MAX_BODY {
+        let error = "Request body too large";
+        return respond(request, 413, serde_json::json!({ "ok": false, "error": error }));
+    }
+    let command = match route(request.method(), request.url(), &body) {
+        Ok(command) => command,
+        Err((code, error)) => {
+            return respond(request, code, serde_json::json!({ "ok": false, "error": error }));
+        }
+    };
+    match control::request(tx, command) {
+        Some(response) => {
+            let code = if response.ok { 200 } else { 400 };
+            respond(request, code, serde_json::to_value(response)?)
+        }
+        None => respond(request, 503, serde_json::json!({ "ok": false, "error": "Player stopped" })),
+    }
+}
+
+fn route(method: &Method, url: &str, body: &str) -> Result<Command, (u16, String)> {
+    let path = url.split('?').next().unwrap_or(url).trim_end_matches('/');
+    let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    match (method, parts.as_slice()) {
+        (Method::Get, ["api", "state"]) => Ok(Command::Status),
+        (Method::Get, ["api", "sounds"]) => Ok(Command::Sounds),
+        (Method::Get, ["api", "categories"]) => Ok(Command::Categories),
+        (Method::Get, ["api", "presets"]) => Ok(Command::Presets),
+        (Method::Post, ["api", "presets", name, "load"]) => Ok(Command::Load {
+            preset: name.to_string(),
+        }),
+        (Method::Post, ["api", "presets", name, "save"]) => Ok(Command::Save {
+            preset: Some(name.to_string()),
+        }),
+        (Method::Post, ["api", cmd @ ("toggle" | "volume" | "mute" | "pan" | "master" | "pause" | "play" | "toggle_pause" | "save" | "rescan")]) => {
+            // The body holds the arguments of the command named by the url
+            let mut args = if body.trim().is_empty() {
+                Value::Object(Default::default())
+            } else {
+                serde_json::from_str(body).map_err(|e| (400, e.to_string()))?
+            };
+            let Value::Object(fields) = &mut args else {
+                return Err((400, "Expected a json object".to_string()));
+            };
+            fields.insert("cmd".to_string(), Value::String(cmd.to_string()));
+            serde_json::from_value(args).map_err(|e| (400, e.to_string()))
+        }
+        _ => Err((404, format!("No route for {} {}", method, path))),
+    }
+}
+
+fn respond(request: Request, code: u16, body: Value) -> std::io::Result<()> {
+    let content_type = Header::from_bytes("Content-Type", "application/json").expect("valid header");
+    request.respond(
+        Response::from_string(body.to_string())
+            .with_status_code(code)
+            .with_header(content_type),
+    )
+}
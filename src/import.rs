@@ -0,0 +1,297 @@
+use crate::files::write_atomic;
+use crate::meter::decibels;
+use crate::sound_manager::SOUND_EXTENSIONS;
+use cli_log::*;
+use rodio::source::Source;
+use rodio::Decoder;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+
+// Loudness the normalised sounds are brought to, in dBFS
+const NORMALIZE_RMS_DB: f32 = -24.0;
+// Highest peak left by the normalisation, in dBFS
+const PEAK_CEILING_DB: f32 = -1.0;
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Could not decode {0}: {1}")]
+    DecoderError(PathBuf, rodio::decoder::DecoderError),
+    #[error("{0} has no sound")]
+    Silent(PathBuf),
+    #[error("{0} is not a mp3, wav or flac file")]
+    Unsupported(PathBuf),
+    #[error("'{0}' is not a valid name")]
+    InvalidName(String),
+    #[error("{0} already exists, --force replaces it")]
+    AlreadyExists(PathBuf),
+    #[error("Nothing left after trimming {0}")]
+    EmptyTrim(PathBuf),
+    #[error("Wav error: {0}")]
+    WavError(#[from] hound::Error),
+    #[error("Invalid manifest {0}: {1}")]
+    Manifest(PathBuf, serde_json::Error),
+}
+
+/// How a sound is imported
+#[derive(Debug, Default)]
+pub struct ImportOptions {
+    pub category: String,
+    /// Shown in the sounds list, the file name when None
+    pub name: Option<String>,
+    /// Link to the file instead of copying it
+    pub link: bool,
+    /// Seconds kept of the file
+    pub start: Option<f32>,
+    pub end: Option<f32>,
+    pub normalize: bool,
+    /// Replaces a sound of the same name
+    pub force: bool,
+}
+
+impl ImportOptions {
+    // Whether the sound is decoded and written again as wav
+    fn transcodes(&self) -> bool {
+        self.start.is_some() || self.end.is_some() || self.normalize
+    }
+}
+
+/// `manifest.json` of the user sounds folder, where the imported sounds come from
+///
+/// ```json
+/// {"sounds": [{"name": "Rain on tin roof", "category": "rain",
+///              "file": "rain/rain-on-tin-roof.mp3", "source": "/home/me/tin-roof.mp3"}]}
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Manifest {
+    pub sounds: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Shown in the sounds list
+    pub name: String,
+    pub category: String,
+    /// Path of the sound in the user sounds folder
+    pub file: String,
+    /// File it was imported from
+    pub source: PathBuf,
+    #[serde(default)]
+    pub linked: bool,
+    /// Seconds kept of the source
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<f32>,
+    /// Gain of the normalisation, in dB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gain_db: Option<f32>,
+}
+
+impl Manifest {
+    pub fn path(folder: &Path) -> PathBuf {
+        folder.join("manifest.json")
+    }
+
+    /// The manifest of a sounds folder, empty if there is none
+    pub fn load(folder: &Path) -> Result<Manifest, ImportError> {
+        let path = Manifest::path(folder);
+        match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| ImportError::Manifest(path, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Manifest::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, folder: &Path) -> Result<(), ImportError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| ImportError::Manifest(Manifest::path(folder), e))?;
+        write_atomic(&Manifest::path(folder), json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Name of a sound given by its path in the folder
+    pub fn name(&self, file: &str) -> Option<&str> {
+        self.sounds.iter().find(|s| s.file == file).map(|s| s.name.as_str())
+    }
+}
+
+/// Lower case words joined by dashes, as the file names of the sounds
+pub fn slug(name: &str) -> Result<String, ImportError> {
+    let slug = name
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-");
+    if slug.is_empty() {
+        Err(ImportError::InvalidName(name.to_string()))
+    } else {
+        Ok(slug)
+    }
+}
+
+/// Checks that rodio can play a file, copies or links it into the `category` folder
+/// of the user sounds and records it in the manifest, gives where it went
+pub fn import_file(
+    folder: &Path,
+    source: &Path,
+    options: &ImportOptions,
+) -> Result<PathBuf, ImportError> {
+    let extension = source
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .filter(|ext| SOUND_EXTENSIONS.contains(&ext.as_str()))
+        .ok_or_else(|| ImportError::Unsupported(source.to_path_buf()))?;
+    let source = fs::canonicalize(source)?;
+    let slug_name = match &options.name {
+        Some(name) => slug(name)?,
+        None => slug(&source.file_stem().unwrap_or_default().to_string_lossy())?,
+    };
+    let category = slug(&options.category)?;
+    let extension = if options.transcodes() { "wav".to_string() } else { extension };
+    let stem = format!("{}/{}", category, slug_name);
+    let file = format!("{}.{}", stem, extension);
+    // Without a name the sound is shown by its file name, as the other ones
+    let name = options.name.clone().unwrap_or(format!("{}.{}", slug_name, extension));
+    let target = folder.join(&file);
+    // The same sound in any format
+    let existing: Vec<String> = SOUND_EXTENSIONS
+        .iter()
+        .map(|ext| format!("{}.{}", stem, ext))
+        .filter(|file| folder.join(file).symlink_metadata().is_ok())
+        .collect();
+    if let Some(file) = existing.first().filter(|_| !options.force) {
+        return Err(ImportError::AlreadyExists(folder.join(file)));
+    }
+    // Checked before anything is written so that a broken file leaves no trace
+    let mut manifest = Manifest::load(folder)?;
+    if decode(&source)?.next().is_none() {
+        return Err(ImportError::Silent(source));
+    }
+    fs::create_dir_all(target.parent().unwrap_or(folder))?;
+    // Written aside and renamed over the sound it replaces, so that the watched folder
+    // never holds a partial file and a failed import keeps the old sound
+    let tmp = temporary_path(&target);
+    let written = if options.transcodes() {
+        transcode(&source, &tmp, options)
+    } else if options.link {
+        std::os::unix::fs::symlink(&source, &tmp).map(|_| None).map_err(ImportError::from)
+    } else {
+        fs::copy(&source, &tmp).map(|_| None).map_err(ImportError::from)
+    };
+    let renamed = written.and_then(|gain_db| {
+        fs::rename(&tmp, &target)?;
+        Ok(gain_db)
+    });
+    let gain_db = match renamed {
+        Ok(gain_db) => gain_db,
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+    };
+    for replaced in existing.iter().filter(|replaced| **replaced != file) {
+        fs::remove_file(folder.join(replaced))?;
+    }
+    info!("Imported {} to {}", source.display(), target.display());
+
+    manifest.sounds.retain(|s| s.file != file && !existing.contains(&s.file));
+    manifest.sounds.push(ManifestEntry {
+        name,
+        category,
+        file,
+        source,
+        linked: options.link,
+        start: options.start,
+        end: options.end,
+        gain_db,
+    });
+    manifest.save(folder)?;
+    Ok(target)
+}
+
+// Hidden and without a sound extension, so that the sounds list never shows it
+fn temporary_path(target: &Path) -> PathBuf {
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    target.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+// Opens a file with rodio
+fn decode(path: &Path) -> Result<Decoder<BufReader<File>>, ImportError> {
+    let file = BufReader::new(File::open(path)?);
+    Decoder::new(file).map_err(|e| ImportError::DecoderError(path.to_path_buf(), e))
+}
+
+// The samples of the source kept by the trim options
+fn trimmed(
+    source: &Path,
+    options: &ImportOptions,
+) -> Result<impl Source<Item = f32>, ImportError> {
+    let seconds = |seconds: f32| Duration::from_secs_f32(seconds.max(0.0));
+    let start = options.start.map_or(Duration::ZERO, seconds);
+    let length = options.end.map_or(Duration::MAX, |end| seconds(end).saturating_sub(start));
+    Ok(decode(source)?.convert_samples().skip_duration(start).take_duration(length))
+}
+
+// Writes the trimmed and normalised sound as a 16 bits wav, gives the gain applied in dB
+fn transcode(
+    source: &Path,
+    target: &Path,
+    options: &ImportOptions,
+) -> Result<Option<f32>, ImportError> {
+    let empty = || ImportError::EmptyTrim(source.to_path_buf());
+    if options.start.zip(options.end).is_some_and(|(start, end)| start >= end) {
+        return Err(empty());
+    }
+    // The levels are measured on a first pass, the file being streamed both times
+    let gain_db = if options.normalize { normalize_gain(trimmed(source, options)?) } else { None };
+    let gain = gain_db.map_or(1.0, |db| 10f32.powf(db / 20.0));
+
+    let samples = trimmed(source, options)?;
+    let channels = samples.channels();
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate: samples.sample_rate(),
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(target, spec)?;
+    // Whole frames only, so that the channels stay in place
+    let mut frame = Vec::with_capacity(channels as usize);
+    let mut frames = 0;
+    for sample in samples {
+        frame.push(sample * gain);
+        if frame.len() == channels as usize {
+            for sample in frame.drain(..) {
+                writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+            }
+            frames += 1;
+        }
+    }
+    writer.finalize()?;
+    if frames == 0 {
+        return Err(empty());
+    }
+    Ok(gain_db)
+}
+
+// Gain bringing the RMS to the one of the normalised sounds, without letting the peaks
+// go above the ceiling, in dB (None for silence)
+fn normalize_gain(samples: impl Iterator<Item = f32>) -> Option<f32> {
+    let (mut peak, mut squares, mut count) = (0.0f32, 0.0f64, 0u64);
+    for sample in samples {
+        peak = peak.max(sample.abs());
+        squares += (sample * sample) as f64;
+        count += 1;
+    }
+    let rms = (squares / count.max(1) as f64).sqrt() as f32;
+    Some((NORMALIZE_RMS_DB - decibels(rms)?).min(PEAK_CEILING_DB - decibels(peak)?))
+}
@@ -0,0 +1,457 @@
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use std::fmt;
+use thiserror::Error;
+
+// Config names of the category actions, by number
+const CATEGORY_NAMES: [&str; 9] = [
+    "category_1", "category_2", "category_3", "category_4", "category_5", "category_6",
+    "category_7", "category_8", "category_9",
+];
+const DIGIT_KEYS: [&[&str]; 9] =
+    [&["1"], &["2"], &["3"], &["4"], &["5"], &["6"], &["7"], &["8"], &["9"]];
+
+/// Everything the user can do from the keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    ToggleSound,
+    RemoveSound,
+    Favourite,
+    TogglePause,
+    SwitchFocus,
+    VolumeDown,
+    VolumeUp,
+    MasterDown,
+    MasterUp,
+    Mute,
+    PanLeft,
+    PanRight,
+    SelectNext,
+    SelectPrevious,
+    SelectLast,
+    NextCategory,
+    PreviousCategory,
+    /// All the sounds, as the first tab
+    ShowAll,
+    /// One of the first nine categories, from 1
+    ShowCategory(u8),
+    Search,
+    EditTags,
+    FilterTags,
+    SaveFilter,
+    DeleteFilter,
+    Save,
+    Undo,
+    Redo,
+    Compact,
+    Help,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 38] = [
+        Action::ToggleSound,
+        Action::RemoveSound,
+        Action::Favourite,
+        Action::TogglePause,
+        Action::SwitchFocus,
+        Action::VolumeDown,
+        Action::VolumeUp,
+        Action::MasterDown,
+        Action::MasterUp,
+        Action::Mute,
+        Action::PanLeft,
+        Action::PanRight,
+        Action::SelectNext,
+        Action::SelectPrevious,
+        Action::SelectLast,
+        Action::NextCategory,
+        Action::PreviousCategory,
+        Action::ShowAll,
+        Action::ShowCategory(1),
+        Action::ShowCategory(2),
+        Action::ShowCategory(3),
+        Action::ShowCategory(4),
+        Action::ShowCategory(5),
+        Action::ShowCategory(6),
+        Action::ShowCategory(7),
+        Action::ShowCategory(8),
+        Action::ShowCategory(9),
+        Action::Search,
+        Action::EditTags,
+        Action::FilterTags,
+        Action::SaveFilter,
+        Action::DeleteFilter,
+        Action::Save,
+        Action::Undo,
+        Action::Redo,
+        Action::Compact,
+        Action::Help,
+        Action::Quit,
+    ];
+
+    /// Groups of the help screen, in display order
+    pub const CONTEXTS: [&'static str; 5] = ["General", "List", "Mixer", "Search", "Presets"];
+
+    /// Name used in the config file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::ToggleSound => "toggle_sound",
+            Action::RemoveSound => "remove_sound",
+            Action::Favourite => "favourite",
+            Action::TogglePause => "toggle_pause",
+            Action::SwitchFocus => "switch_focus",
+            Action::VolumeDown => "volume_down",
+            Action::VolumeUp => "volume_up",
+            Action::MasterDown => "master_down",
+            Action::MasterUp => "master_up",
+            Action::Mute => "mute",
+            Action::PanLeft => "pan_left",
+            Action::PanRight => "pan_right",
+            Action::SelectNext => "select_next",
+            Action::SelectPrevious => "select_previous",
+            Action::SelectLast => "select_last",
+            Action::NextCategory => "next_category",
+            Action::PreviousCategory => "previous_category",
+            Action::ShowAll => "show_all",
+            Action::ShowCategory(n) => CATEGORY_NAMES[(*n as usize).clamp(1, 9) - 1],
+            Action::Search => "search",
+            Action::EditTags => "edit_tags",
+            Action::FilterTags => "filter_tags",
+            Action::SaveFilter => "save_filter",
+            Action::DeleteFilter => "delete_filter",
+            Action::Save => "save",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Compact => "compact",
+            Action::Help => "help",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::ToggleSound => "Add/Remove the selected sound",
+            Action::RemoveSound => "Remove the selected sound from the mix",
+            Action::Favourite => "Add/Remove the selected sound from the favourites",
+            Action::TogglePause => "Pause/play",
+            Action::SwitchFocus => "Move between the sounds list and the mixer",
+            Action::VolumeDown => "Lower the volume of the selected sound",
+            Action::VolumeUp => "Raise the volume of the selected sound",
+            Action::MasterDown => "Lower the master volume",
+            Action::MasterUp => "Raise the master volume",
+            Action::Mute => "Mute/unmute the selected sound",
+            Action::PanLeft => "Pan the selected sound to the left",
+            Action::PanRight => "Pan the selected sound to the right",
+            Action::SelectNext => "Select the next sound",
+            Action::SelectPrevious => "Select the previous sound",
+            Action::SelectLast => "Select the last sound",
+            Action::NextCategory => "Next category",
+            Action::PreviousCategory => "Previous category",
+            Action::ShowAll => "Show all the sounds",
+            Action::ShowCategory(_) => "Show the category of that number",
+            Action::Search => "Search the sounds, Enter toggles the first result and Esc cancels",
+            Action::EditTags => "Edit the tags of the selected sound",
+            Action::FilterTags => "Filter by tags, e.g. `rain -storm forest|beach`, Esc clears",
+            Action::SaveFilter => "Save the tag filter as a category",
+            Action::DeleteFilter => "Delete the saved filter shown",
+            Action::Save => "Save the mix",
+            Action::Undo => "Undo the last change of the mix",
+            Action::Redo => "Redo the last undone change",
+            Action::Compact => "Switch to the one line view and back",
+            Action::Help => "Show this help",
+            Action::Quit => "Quit (or detach from the daemon)",
+        }
+    }
+
+    /// Group of the action on the help screen, one of `CONTEXTS`
+    pub fn context(&self) -> &'static str {
+        match self {
+            Action::TogglePause
+            | Action::MasterDown
+            | Action::MasterUp
+            | Action::SwitchFocus
+            | Action::Undo
+            | Action::Redo
+            | Action::Compact
+            | Action::Help
+            | Action::Quit => "General",
+            Action::ToggleSound
+            | Action::Favourite
+            | Action::EditTags
+            | Action::SelectNext
+            | Action::SelectPrevious
+            | Action::SelectLast
+            | Action::NextCategory
+            | Action::PreviousCategory
+            | Action::ShowAll
+            | Action::ShowCategory(_) => "List",
+            Action::RemoveSound
+            | Action::VolumeDown
+            | Action::VolumeUp
+            | Action::Mute
+            | Action::PanLeft
+            | Action::PanRight => "Mixer",
+            Action::Search
+            | Action::FilterTags
+            | Action::SaveFilter
+            | Action::DeleteFilter => "Search",
+            Action::Save => "Presets",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|a| a.name() == name)
+    }
+
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::ToggleSound => &["Enter"],
+            Action::RemoveSound => &["x", "Delete"],
+            Action::Favourite => &["f"],
+            Action::TogglePause => &["Space"],
+            Action::SwitchFocus => &["Tab"],
+            Action::VolumeDown => &["Left", "h"],
+            Action::VolumeUp => &["Right", "l"],
+            Action::MasterDown => &["Ctrl+Left"],
+            Action::MasterUp => &["Ctrl+Right"],
+            Action::Mute => &["m"],
+            Action::PanLeft => &["["],
+            Action::PanRight => &["]"],
+            Action::SelectNext => &["Down", "j"],
+            Action::SelectPrevious => &["Up", "k"],
+            Action::SelectLast => &["End", "G"],
+            Action::NextCategory => &["c"],
+            Action::PreviousCategory => &["Shift+Tab"],
+            Action::ShowAll => &["0"],
+            Action::ShowCategory(n) => DIGIT_KEYS[(*n as usize).clamp(1, 9) - 1],
+            Action::Search => &["/"],
+            Action::EditTags => &["t"],
+            Action::FilterTags => &["#"],
+            Action::SaveFilter => &["S"],
+            Action::DeleteFilter => &["D"],
+            Action::Save => &["s"],
+            Action::Undo => &["u"],
+            Action::Redo => &["Ctrl+r"],
+            Action::Compact => &["z"],
+            Action::Help => &["?"],
+            Action::Quit => &["q"],
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum KeymapError {
+    #[error("Unknown action '{0}'")]
+    UnknownAction(String),
+    #[error("Invalid key '{key}' for {action}")]
+    InvalidKey { action: String, key: String },
+    #[error("'{key}' is bound to {first} and again to {second}")]
+    DuplicateKey {
+        key: String,
+        first: &'static str,
+        second: &'static str,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    /// Parses keys such as `q`, `Space`, `Ctrl+Left` or `Alt+Shift+F2`
+    pub fn parse(s: &str) -> Option<KeyBinding> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts: Vec<&str> = s.split('+').collect();
+        // "+" alone or "Ctrl++"
+        let key = if s.ends_with("++") || s == "+" {
+            parts.truncate(parts.len().saturating_sub(2));
+            "+"
+        } else {
+            parts.pop()?
+        };
+        for modifier in parts {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+        }
+        let code = match key.to_lowercase().as_str() {
+            "enter" => KeyCode::Enter,
+            "space" => KeyCode::Char(' '),
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "esc" | "escape" => KeyCode::Esc,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            f if f.len() > 1 && f.starts_with('f') => KeyCode::F(f[1..].parse().ok()?),
+            _ => {
+                let mut chars = key.chars();
+                let c = chars.next()?;
+                if chars.next().is_some() {
+                    return None;
+                }
+                KeyCode::Char(c)
+            }
+        };
+        // Terminals send Shift+Tab as BackTab
+        if code == KeyCode::Tab && modifiers.contains(KeyModifiers::SHIFT) {
+            return Some(KeyBinding {
+                code: KeyCode::BackTab,
+                modifiers: modifiers.difference(KeyModifiers::SHIFT),
+            });
+        }
+        Some(KeyBinding { code, modifiers })
+    }
+
+    fn matches(&self, key: &KeyEvent) -> bool {
+        match (self.code, key.code) {
+            // Shift is already part of the key, 'G' or BackTab come with Shift
+            (KeyCode::Char(a), KeyCode::Char(b)) => {
+                a == b
+                    && self.modifiers.difference(KeyModifiers::SHIFT)
+                        == key.modifiers.difference(KeyModifiers::SHIFT)
+            }
+            (KeyCode::BackTab, KeyCode::BackTab) => {
+                self.modifiers.difference(KeyModifiers::SHIFT)
+                    == key.modifiers.difference(KeyModifiers::SHIFT)
+            }
+            (a, b) => a == b && self.modifiers == key.modifiers,
+        }
+    }
+
+    // Whether both are triggered by the same key press
+    fn overlaps(&self, other: &KeyBinding) -> bool {
+        self.matches(&KeyEvent::new(other.code, other.modifiers))
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::BackTab => write!(f, "Shift+Tab"),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// Active key bindings, the defaults overridden by the config file
+pub struct Keymap {
+    bindings: Vec<(Action, Vec<KeyBinding>)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap {
+            bindings: Action::ALL
+                .iter()
+                .map(|action| {
+                    let keys = action
+                        .default_keys()
+                        .iter()
+                        .map(|k| KeyBinding::parse(k).expect("valid default key"))
+                        .collect();
+                    (*action, keys)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Builds the keymap from the `[keys]` table of the config, e.g. `volume_up = ["l", "Right"]`
+    pub fn new(overrides: &HashMap<String, Vec<String>>) -> Result<Keymap, KeymapError> {
+        let mut keymap = Keymap::default();
+        for (name, keys) in overrides {
+            let action =
+                Action::from_name(name).ok_or_else(|| KeymapError::UnknownAction(name.clone()))?;
+            let keys = keys
+                .iter()
+                .map(|key| {
+                    KeyBinding::parse(key).ok_or_else(|| KeymapError::InvalidKey {
+                        action: name.clone(),
+                        key: key.clone(),
+                    })
+                })
+                .collect::<Result<Vec<KeyBinding>, KeymapError>>()?;
+            if let Some((_, bindings)) = keymap.bindings.iter_mut().find(|(a, _)| *a == action) {
+                *bindings = keys;
+            }
+        }
+        keymap.check_duplicates()?;
+        Ok(keymap)
+    }
+
+    // A key press runs a single action, the first one bound to it would hide the others
+    fn check_duplicates(&self) -> Result<(), KeymapError> {
+        let keys: Vec<(Action, &KeyBinding)> = self
+            .bindings
+            .iter()
+            .flat_map(|(action, keys)| keys.iter().map(move |key| (*action, key)))
+            .collect();
+        for (i, (first, key)) in keys.iter().enumerate() {
+            if let Some((second, _)) = keys[i + 1..].iter().find(|(_, other)| key.overlaps(other)) {
+                return Err(KeymapError::DuplicateKey {
+                    key: key.to_string(),
+                    first: first.name(),
+                    second: second.name(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.iter().any(|k| k.matches(key)))
+            .map(|(action, _)| *action)
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyBinding] {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, keys)| keys.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// First key bound to the action, for the footer
+    pub fn key(&self, action: Action) -> String {
+        match self.keys(action).first() {
+            Some(key) => key.to_string(),
+            None => "(unbound)".to_string(),
+        }
+    }
+}
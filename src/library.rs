@@ -0,0 +1,139 @@
+use crate::files::write_atomic;
+use crate::sound_manager::FileError;
+use cli_log::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+// Number of sounds kept in the Recent category
+const MAX_RECENT: usize = 20;
+
+/// What the user knows about the sounds: favourites, recently played ones and tags
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Library {
+    /// Paths of the starred sounds, in starring order
+    pub favourites: Vec<String>,
+    /// Paths of the last added sounds, the most recent first
+    pub recent: Vec<String>,
+    /// Tags of the sounds, by path
+    pub tags: BTreeMap<String, Vec<String>>,
+    /// Saved tag filters, shown as categories
+    pub filters: Vec<String>,
+    #[serde(skip)]
+    path: PathBuf,
+    // Set when the file could not be read, so that it is never saved over
+    #[serde(skip)]
+    read_only: bool,
+}
+
+impl Library {
+    /// Loads the library, empty if there is none yet, or if it cannot be read in which
+    /// case the changes are not saved
+    pub fn load(path: PathBuf) -> Library {
+        let mut library = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                warn!("Ignoring invalid library {}, it is left as it is: {}", path.display(), e);
+                Library { read_only: true, ..Library::default() }
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Library::default(),
+            Err(e) => {
+                warn!("Cannot read the library {}: {}", path.display(), e);
+                Library { read_only: true, ..Library::default() }
+            }
+        };
+        library.path = path;
+        library
+    }
+
+    pub fn is_favourite(&self, path: &str) -> bool {
+        self.favourites.iter().any(|p| p == path)
+    }
+
+    pub fn toggle_favourite(&mut self, path: &str) {
+        if self.is_favourite(path) {
+            self.favourites.retain(|p| p != path);
+        } else {
+            self.favourites.push(path.to_string());
+        }
+        self.save_or_warn();
+    }
+
+    /// Moves a sound on top of the recent ones
+    pub fn add_recent(&mut self, path: &str) {
+        self.recent.retain(|p| p != path);
+        self.recent.insert(0, path.to_string());
+        self.recent.truncate(MAX_RECENT);
+        self.save_or_warn();
+    }
+
+    pub fn tags(&self, path: &str) -> &[String] {
+        self.tags.get(path).map(|t| t.as_slice()).unwrap_or(&[])
+    }
+
+    /// Replaces the tags of a sound, given as words separated by spaces or commas
+    pub fn set_tags(&mut self, path: &str, tags: &str) {
+        let mut tags: Vec<String> = tags
+            .split([' ', ','])
+            .filter(|t| !t.is_empty())
+            .map(|t| t.trim_start_matches('#').to_lowercase())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        if tags.is_empty() {
+            self.tags.remove(path);
+        } else {
+            self.tags.insert(path.to_string(), tags);
+        }
+        self.save_or_warn();
+    }
+
+    /// Every tag in use, sorted
+    pub fn all_tags(&self) -> Vec<&str> {
+        let mut tags: Vec<&str> = self.tags.values().flatten().map(|t| t.as_str()).collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+
+    pub fn save_filter(&mut self, filter: &str) {
+        let filter = filter.trim();
+        if !filter.is_empty() && !self.filters.iter().any(|f| f == filter) {
+            self.filters.push(filter.to_string());
+            self.save_or_warn();
+        }
+    }
+
+    pub fn remove_filter(&mut self, filter: &str) {
+        self.filters.retain(|f| f != filter);
+        self.save_or_warn();
+    }
+
+    fn save(&self) -> Result<(), FileError> {
+        write_atomic(&self.path, serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    fn save_or_warn(&self) {
+        if self.read_only {
+            warn!("Not saving the library over {}, it could not be read", self.path.display());
+            return;
+        }
+        if let Err(e) = self.save() {
+            warn!("Cannot save the library {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Whether tags match a filter such as `rain -storm forest|beach`:
+/// every word must match, `-tag` excludes a tag and `a|b` accepts either
+pub fn tags_match(filter: &str, tags: &[String]) -> bool {
+    let has = |tag: &str| tags.iter().any(|t| t.eq_ignore_ascii_case(tag.trim_start_matches('#')));
+    filter
+        .split_whitespace()
+        .all(|term| match term.strip_prefix('-') {
+            Some(excluded) => !has(excluded),
+            None => term.split('|').any(has),
+        })
+}
@@ -1,20 +1,56 @@
-use app::App;
+use clap::Parser;
+use cli::{Cli, Command};
 use color_eyre::Result;
-use sound_manager::SoundManager;
 
 mod app;
+mod cli;
+mod config;
+mod control;
+mod daemon;
+mod dirs;
+mod export;
+mod files;
+mod fuzzy;
+mod history;
+mod http;
+mod import;
+mod keymap;
+mod library;
+mod meter;
+#[cfg(feature = "mpris")]
+mod mpris;
+mod osc;
+mod pan;
 mod sink_handle;
 mod sound;
 mod sound_manager;
+mod theme;
+mod view;
+mod watcher;
 
 fn main() -> Result<()> {
     cli_log::init_cli_log!();
-    let app = App::new(SoundManager::new());
     color_eyre::install()?;
-    let terminal = ratatui::init();
-    let app_result = app.run(terminal);
-    ratatui::restore();
-    app_result
+    let cli = Cli::parse();
+    cli.dirs().migrate();
+    if let Some(format) = cli.status {
+        return cli::status(&cli, format);
+    }
+    match &cli.command {
+        Some(Command::Play { preset, duration }) => cli::play(&cli, preset.clone(), *duration),
+        Some(Command::List) => cli::list(&cli),
+        Some(Command::Keys) => cli::keys(&cli),
+        Some(Command::Paths) => cli::paths(&cli),
+        Some(Command::Export {
+            preset,
+            output,
+            duration,
+        }) => cli::export(&cli, preset, output, *duration),
+        Some(Command::Import(args)) => cli::import(&cli, args),
+        Some(Command::Daemon { preset, detach }) => cli::daemon(&cli, preset.clone(), *detach),
+        Some(Command::Send { command }) => cli::send(&cli, command),
+        None => cli::tui(&cli),
+    }
 }
 
 //Todo
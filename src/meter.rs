@@ -0,0 +1,177 @@
+use rodio::Source;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Samples measured at once, about 20ms of stereo sound
+const BLOCK: usize = 2048;
+/// Samples of a block kept for the waveform, evenly spaced
+pub const WAVE_POINTS: usize = 64;
+// Samples between two points of the waveform, even so that they are all of the left channel
+const WAVE_STEP: usize = BLOCK / WAVE_POINTS;
+// Lowest level shown by the meters
+const FLOOR_DB: f32 = -60.0;
+
+/// Peak and RMS of a block of samples, 1 is full scale
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Level {
+    pub peak: f32,
+    pub rms: f32,
+}
+
+impl Level {
+    pub fn scaled(self, gain: f32) -> Level {
+        Level {
+            peak: self.peak * gain,
+            rms: self.rms * gain,
+        }
+    }
+
+    /// Level of layers played together, their RMS adding up as uncorrelated noises
+    pub fn mix(levels: &[Level]) -> Level {
+        Level {
+            peak: levels.iter().map(|l| l.peak).sum(),
+            rms: levels.iter().map(|l| l.rms * l.rms).sum::<f32>().sqrt(),
+        }
+    }
+}
+
+/// Level in dBFS, None below the floor of the meters
+pub fn decibels(value: f32) -> Option<f32> {
+    let db = 20.0 * value.log10();
+    (db > FLOOR_DB).then_some(db)
+}
+
+/// Position of a level on a meter, from 0 at the floor to 1 at full scale
+pub fn meter_ratio(value: f32) -> f64 {
+    decibels(value).map_or(0.0, |db| ((db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0) as f64)
+}
+
+/// Levels and waveform measured by the audio thread, shared with the user interface
+#[derive(Clone, Default)]
+pub struct Meter(Arc<Measures>);
+
+struct Measures {
+    level: [AtomicU32; 2],
+    wave: [AtomicU32; WAVE_POINTS],
+}
+
+impl Default for Measures {
+    fn default() -> Self {
+        Measures {
+            level: Default::default(),
+            wave: std::array::from_fn(|_| AtomicU32::default()),
+        }
+    }
+}
+
+impl Meter {
+    pub fn level(&self) -> Level {
+        Level {
+            peak: f32::from_bits(self.0.level[0].load(Ordering::Relaxed)),
+            rms: f32::from_bits(self.0.level[1].load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Samples of the last block, between -1 and 1
+    pub fn wave(&self) -> Vec<f32> {
+        self.0.wave.iter().map(|point| f32::from_bits(point.load(Ordering::Relaxed))).collect()
+    }
+
+    pub fn reset(&self) {
+        self.store(Level::default(), &[0.0; WAVE_POINTS]);
+    }
+
+    fn store(&self, level: Level, wave: &[f32; WAVE_POINTS]) {
+        self.0.level[0].store(level.peak.to_bits(), Ordering::Relaxed);
+        self.0.level[1].store(level.rms.to_bits(), Ordering::Relaxed);
+        for (point, sample) in self.0.wave.iter().zip(wave) {
+            point.store(sample.to_bits(), Ordering::Relaxed);
+        }
+    }
+}
+
+/// The waveforms of layers played together, scaled by their volume
+pub fn mix_waves(waves: &[(Vec<f32>, f32)]) -> Vec<f32> {
+    let mut mix = vec![0.0; WAVE_POINTS];
+    for (wave, volume) in waves {
+        mix.iter_mut().zip(wave).for_each(|(mixed, sample)| *mixed += sample * volume);
+    }
+    mix
+}
+
+/// Measures the samples going through it, by blocks
+pub struct Metered<S> {
+    input: S,
+    meter: Meter,
+    peak: f32,
+    squares: f32,
+    count: usize,
+    wave: [f32; WAVE_POINTS],
+}
+
+impl<S> Metered<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, meter: Meter) -> Self {
+        Metered {
+            input,
+            meter,
+            peak: 0.0,
+            squares: 0.0,
+            count: 0,
+            wave: [0.0; WAVE_POINTS],
+        }
+    }
+}
+
+impl<S> Iterator for Metered<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        self.peak = self.peak.max(sample.abs());
+        self.squares += sample * sample;
+        if self.count.is_multiple_of(WAVE_STEP) {
+            self.wave[self.count / WAVE_STEP] = sample;
+        }
+        self.count += 1;
+        if self.count == BLOCK {
+            let level = Level {
+                peak: self.peak,
+                rms: (self.squares / BLOCK as f32).sqrt(),
+            };
+            self.meter.store(level, &self.wave);
+            self.peak = 0.0;
+            self.squares = 0.0;
+            self.count = 0;
+        }
+        Some(sample)
+    }
+}
+
+impl<S> Source for Metered<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
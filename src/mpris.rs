@@ -0,0 +1,386 @@
+//! MPRIS interface, so desktop media keys and panel widgets can control serenIT.
+//!
+//! The bus is the one given by `DBUS_SESSION_BUS_ADDRESS`, start serenIT under
+//! `dbus-run-session` to try it on a private session bus.
+
+use crate::control::{request, Command, ControlRequest, Status};
+use cli_log::*;
+use std::collections::HashMap;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+use zbus::blocking::connection;
+use zbus::interface;
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.serenit";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const TRACK_ID: &str = "/org/mpris/MediaPlayer2/serenit/mix";
+// How often the status is polled to notify the clients of changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Publishes the player on the session bus, in the background
+pub fn start(tx: Sender<ControlRequest>) {
+    thread::spawn(move || {
+        if let Err(e) = serve(tx) {
+            warn!("MPRIS unavailable: {}", e);
+        }
+    });
+}
+
+fn serve(tx: Sender<ControlRequest>) -> zbus::Result<()> {
+    let connection = with_interfaces(connection::Builder::session()?.name(BUS_NAME)?, &tx)?
+        .build()?;
+    let player = connection
+        .object_server()
+        .interface::<_, Player>(OBJECT_PATH)?;
+
+    let mut last = Status::default();
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        // The owner of the sound manager is gone
+        let Some(response) = request(&tx, Command::Status) else {
+            return Ok(());
+        };
+        let status = response.status;
+        let emitter = player.signal_emitter();
+        let iface = player.get();
+        if status.paused != last.paused {
+            zbus::block_on(iface.playback_status_changed(emitter))?;
+        }
+        if status.master_volume != last.master_volume {
+            zbus::block_on(iface.volume_changed(emitter))?;
+        }
+        if status.preset != last.preset || layer_names(&status) != layer_names(&last) {
+            zbus::block_on(iface.metadata_changed(emitter))?;
+        }
+        last = status;
+    }
+}
+
+// Serves the root and player interfaces on the connection being built
+fn with_interfaces<'a>(
+    builder: connection::Builder<'a>,
+    tx: &Sender<ControlRequest>,
+) -> zbus::Result<connection::Builder<'a>> {
+    builder
+        .serve_at(OBJECT_PATH, Root { tx: tx.clone() })?
+        .serve_at(OBJECT_PATH, Player { tx: tx.clone() })
+}
+
+fn layer_names(status: &Status) -> Vec<String> {
+    status
+        .layers
+        .iter()
+        .map(|layer| {
+            std::path::Path::new(&layer.source)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+fn owned(value: Value<'_>) -> OwnedValue {
+    value.try_to_owned().expect("metadata values hold no file descriptor")
+}
+
+struct Root {
+    tx: Sender<ControlRequest>,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {}
+
+    fn quit(&self) {
+        request(&self.tx, Command::Quit);
+    }
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        "serenIT".to_string()
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec![]
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+struct Player {
+    tx: Sender<ControlRequest>,
+}
+
+impl Player {
+    fn status(&self) -> Status {
+        request(&self.tx, Command::Status)
+            .map(|response| response.status)
+            .unwrap_or_default()
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn play_pause(&self) {
+        request(&self.tx, Command::TogglePause);
+    }
+
+    fn play(&self) {
+        request(&self.tx, Command::Play);
+    }
+
+    fn pause(&self) {
+        request(&self.tx, Command::Pause);
+    }
+
+    // Ambient sounds loop forever, stopping is pausing
+    fn stop(&self) {
+        request(&self.tx, Command::Pause);
+    }
+
+    fn next(&self) {}
+
+    fn previous(&self) {}
+
+    fn seek(&self, _offset: i64) {}
+
+    fn set_position(&self, _track_id: ObjectPath<'_>, _position: i64) {}
+
+    fn open_uri(&self, _uri: &str) {}
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        if self.status().paused {
+            "Paused".to_string()
+        } else {
+            "Playing".to_string()
+        }
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn set_rate(&mut self, _rate: f64) {}
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let status = self.status();
+        let title = status.preset.clone().unwrap_or("Custom mix".to_string());
+        let mut metadata = HashMap::new();
+        metadata.insert(
+            "mpris:trackid".to_string(),
+            owned(Value::from(ObjectPath::from_static_str_unchecked(TRACK_ID))),
+        );
+        metadata.insert("xesam:title".to_string(), owned(Value::from(title)));
+        metadata.insert(
+            "xesam:artist".to_string(),
+            owned(Value::from(layer_names(&status))),
+        );
+        metadata
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.status().master_volume as f64
+    }
+
+    #[zbus(property)]
+    fn set_volume(&mut self, volume: f64) {
+        request(
+            &self.tx,
+            Command::Master {
+                volume: volume.clamp(0.0, 1.0) as f32,
+            },
+        );
+    }
+
+    #[zbus(property)]
+    fn position(&self) -> i64 {
+        0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::Response;
+    use crate::sound_manager::SoundData;
+    use std::os::unix::net::UnixStream;
+    use std::sync::mpsc::{self, Receiver};
+    use zbus::blocking::{proxy, Proxy};
+    use zbus::proxy::CacheProperties;
+    use zbus::Guid;
+
+    // A sender answering with the given status, the commands it receives being recorded
+    fn answering_tx(status: Status) -> (Sender<ControlRequest>, Receiver<Command>) {
+        let (tx, rx) = mpsc::channel::<ControlRequest>();
+        let (commands_tx, commands) = mpsc::channel();
+        thread::spawn(move || {
+            for request in rx {
+                let _ = commands_tx.send(request.command);
+                let _ = request.reply.send(Response {
+                    ok: true,
+                    error: None,
+                    status: status.clone(),
+                    data: None,
+                });
+            }
+        });
+        (tx, commands)
+    }
+
+    // A player answering with the given status, the commands it receives being recorded
+    fn answering(status: Status) -> (Player, Receiver<Command>) {
+        let (tx, commands) = answering_tx(status);
+        (Player { tx }, commands)
+    }
+
+    #[test]
+    fn methods_map_to_commands() {
+        let (mut player, commands) = answering(Status::default());
+        player.play_pause();
+        assert!(matches!(commands.recv().unwrap(), Command::TogglePause));
+        player.play();
+        assert!(matches!(commands.recv().unwrap(), Command::Play));
+        player.pause();
+        assert!(matches!(commands.recv().unwrap(), Command::Pause));
+        player.stop();
+        assert!(matches!(commands.recv().unwrap(), Command::Pause));
+        player.set_volume(1.5);
+        assert!(matches!(commands.recv().unwrap(), Command::Master { volume } if volume == 1.0));
+        player.set_volume(0.25);
+        assert!(matches!(commands.recv().unwrap(), Command::Master { volume } if volume == 0.25));
+    }
+
+    #[test]
+    fn properties_follow_the_status() {
+        let status = Status {
+            paused: true,
+            master_volume: 0.5,
+            preset: Some("rainy".to_string()),
+            layers: vec![SoundData {
+                source: "./sounds/rain/light-rain.mp3".to_string(),
+                volume: 0.5,
+                muted: false,
+                pan: 0.0,
+            }],
+            ..Status::default()
+        };
+        let (player, _commands) = answering(status);
+        assert_eq!(player.playback_status(), "Paused");
+        assert_eq!(player.volume(), 0.5);
+        let metadata = player.metadata();
+        assert_eq!(metadata["xesam:title"], owned(Value::from("rainy")));
+        assert_eq!(metadata["xesam:artist"], owned(Value::from(vec!["light-rain".to_string()])));
+
+        let (player, _commands) = answering(Status::default());
+        assert_eq!(player.playback_status(), "Playing");
+        assert_eq!(player.metadata()["xesam:title"], owned(Value::from("Custom mix")));
+    }
+
+    #[test]
+    fn serves_the_player_over_dbus() {
+        let status = Status {
+            master_volume: 0.5,
+            ..Status::default()
+        };
+        let (tx, commands) = answering_tx(status);
+        // A peer to peer connection stands for the session bus
+        let (server, client) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || {
+            let builder = connection::Builder::async_io_unix_stream(server)
+                .server(Guid::generate())
+                .unwrap()
+                .p2p();
+            with_interfaces(builder, &tx).unwrap().build().unwrap()
+        });
+        let client = connection::Builder::async_io_unix_stream(client)
+            .p2p()
+            .build()
+            .unwrap();
+        let _server = server.join().unwrap();
+        let player = proxy::Builder::<Proxy>::new(&client)
+            .destination(BUS_NAME)
+            .unwrap()
+            .path(OBJECT_PATH)
+            .unwrap()
+            .interface("org.mpris.MediaPlayer2.Player")
+            .unwrap()
+            .cache_properties(CacheProperties::No)
+            .build()
+            .unwrap();
+
+        player.call::<_, _, ()>("PlayPause", &()).unwrap();
+        assert!(matches!(commands.recv().unwrap(), Command::TogglePause));
+        assert_eq!(player.get_property::<f64>("Volume").unwrap(), 0.5);
+        assert!(matches!(commands.recv().unwrap(), Command::Status));
+        player.set_property("Volume", 0.25).unwrap();
+        assert!(matches!(commands.recv().unwrap(), Command::Master { volume } if volume == 0.25));
+        let status: String = player.get_property("PlaybackStatus").unwrap();
+        assert_eq!(status, "Playing");
+    }
+}
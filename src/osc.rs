@@ -0,0 +1,292 @@
+//! Open Sound Control input over UDP, for lighting/VJ rigs and TouchOSC layouts.
+//!
+//! ```text
+//! /serenit/layer/<name>/volume  f   volume of a sound, by name ("light-rain") or path
+//! /serenit/layer/<name>/toggle
+//! /serenit/layer/<name>/mute    [i]  1 mutes and 0 unmutes, mutes without argument
+//! /serenit/layer/<name>/pan     f    -1 is left and 1 right
+//! /serenit/master               f
+//! /serenit/preset/load          s
+//! /serenit/pause                [i]  toggles without argument, 1 pauses and 0 plays
+//! ```
+
+use crate::control::{self, Command, ControlRequest};
+use cli_log::*;
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use std::net::UdpSocket;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:9000";
+// Bundles in bundles accepted, real senders hardly nest them at all
+const MAX_BUNDLE_DEPTH: usize = 8;
+
+#[derive(Debug)]
+enum Arg {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+    // Blobs, nil, impulse... nothing we use
+    Other,
+}
+
+#[derive(Debug)]
+struct Message {
+    address: String,
+    args: Vec<Arg>,
+}
+
+/// Listens for OSC packets in the background
+pub fn start(address: &str, tx: Sender<ControlRequest>) -> Result<()> {
+    let socket = UdpSocket::bind(address).map_err(|e| eyre!("Cannot listen on {}: {}", address, e))?;
+    info!("OSC listening on {}", address);
+    thread::spawn(move || {
+        let mut buf = [0u8; 65536];
+        while let Ok((len, _)) = socket.recv_from(&mut buf) {
+            let messages = match decode(&buf[..len]) {
+                Ok(messages) => messages,
+                Err(e) => {
+                    warn!("Invalid OSC packet: {}", e);
+                    continue;
+                }
+            };
+            for message in messages {
+                match to_command(&message) {
+                    // Faders send a lot of messages, do not wait for each answer
+                    Some(command) => control::send(&tx, command),
+                    None => warn!("Unknown OSC message: {:?}", message),
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+fn to_command(message: &Message) -> Option<Command> {
+    let parts: Vec<&str> = message.address.trim_start_matches('/').split('/').collect();
+    let first = message.args.first();
+    match parts.as_slice() {
+        ["serenit", "layer", name, "volume"] => Some(Command::Volume {
+            path: name.to_string(),
+            volume: number(first?)?.clamp(0.0, 1.0),
+        }),
+        ["serenit", "layer", name, "toggle"] => Some(Command::Toggle {
+            path: name.to_string(),
+        }),
+        ["serenit", "layer", name, "mute"] => Some(Command::Mute {
+            path: name.to_string(),
+            muted: match first {
+                None | Some(Arg::Bool(true)) => true,
+                Some(Arg::Bool(false)) => false,
+                Some(arg) => number(arg)? > 0.0,
+            },
+        }),
+        ["serenit", "layer", name, "pan"] => Some(Command::Pan {
+            path: name.to_string(),
+            pan: number(first?)?.clamp(-1.0, 1.0),
+        }),
+        ["serenit", "master"] => Some(Command::Master {
+            volume: number(first?)?.clamp(0.0, 1.0),
+        }),
+        ["serenit", "preset", "load"] => match first? {
+            Arg::Str(preset) => Some(Command::Load {
+                preset: preset.clone(),
+            }),
+            _ => None,
+        },
+        ["serenit", "pause"] => match first {
+            None => Some(Command::TogglePause),
+            Some(Arg::Bool(true)) => Some(Command::Pause),
+            Some(Arg::Bool(false)) => Some(Command::Play),
+            Some(arg) => {
+                if number(arg)? > 0.0 {
+                    Some(Command::Pause)
+                } else {
+                    Some(Command::Play)
+                }
+            }
+        },
+        _ => None,
+    }
+}
+
+fn number(arg: &Arg) -> Option<f32> {
+    match arg {
+        Arg::Int(i) => Some(*i as f32),
+        Arg::Float(f) => Some(*f as f32),
+        _ => None,
+    }
+}
+
+/// Decodes a packet, a bundle gives all its messages
+fn decode(packet: &[u8]) -> Result<Vec<Message>, String> {
+    decode_nested(packet, 0)
+}
+
+// Bundles may hold bundles, `depth` of them around this packet
+fn decode_nested(packet: &[u8], depth: usize) -> Result<Vec<Message>, String> {
+    let mut reader = Reader { data: packet, pos: 0 };
+    let address = reader.string()?;
+    if address == "#bundle" {
+        // Each level is a call, a packet must not be able to exhaust the stack
+        if depth == MAX_BUNDLE_DEPTH {
+            return Err(format!("Bundles nested more than {} deep", MAX_BUNDLE_DEPTH));
+        }
+        // Time tags are ignored, everything applies right away
+        reader.take(8)?;
+        let mut messages = vec![];
+        while reader.pos < packet.len() {
+            let size = reader.size()?;
+            messages.extend(decode_nested(reader.take(size)?, depth + 1)?);
+        }
+        return Ok(messages);
+    }
+    let mut args = vec![];
+    // Old implementations may omit the type tags
+    if reader.pos < packet.len() {
+        let tags = reader.string()?;
+        for tag in tags.trim_start_matches(',').chars() {
+            args.push(match tag {
+                'i' => Arg::Int(reader.int()? as i64),
+                'h' => Arg::Int(i64::from_be_bytes(reader.take(8)?.try_into().unwrap())),
+                'f' => Arg::Float(f32::from_bits(reader.int()? as u32) as f64),
+                'd' => Arg::Float(f64::from_bits(u64::from_be_bytes(
+                    reader.take(8)?.try_into().unwrap(),
+                ))),
+                's' | 'S' => Arg::Str(reader.string()?),
+                'T' => Arg::Bool(true),
+                'F' => Arg::Bool(false),
+                'b' => {
+                    let size = reader.size()?;
+                    let padded = size.div_ceil(4).checked_mul(4).ok_or("Invalid blob size")?;
+                    reader.take(padded)?;
+                    Arg::Other
+                }
+                'c' | 'r' | 'm' => {
+                    reader.take(4)?;
+                    Arg::Other
+                }
+                't' => {
+                    reader.take(8)?;
+                    Arg::Other
+                }
+                'N' | 'I' | '[' | ']' => Arg::Other,
+                _ => return Err(format!("Unknown type tag '{}'", tag)),
+            });
+        }
+    }
+    Ok(vec![Message { address, args }])
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(len).ok_or("Truncated packet")?;
+        let bytes = self.data.get(self.pos..end).ok_or("Truncated packet")?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    // Sizes of the bundle elements and blobs, negative ones are invalid
+    fn size(&mut self) -> Result<usize, String> {
+        let size = self.int()?;
+        usize::try_from(size).map_err(|_| format!("Invalid size {}", size))
+    }
+
+    fn int(&mut self) -> Result<i32, String> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    // Strings are null terminated and padded to 4 bytes
+    fn string(&mut self) -> Result<String, String> {
+        let rest = &self.data[self.pos..];
+        let len = rest
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or("Unterminated string")?;
+        let s = String::from_utf8_lossy(&rest[..len]).to_string();
+        self.take((len + 1).div_ceil(4) * 4)?;
+        Ok(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An OSC string, null terminated and padded to 4 bytes
+    fn string(s: &str) -> Vec<u8> {
+        let mut bytes = s.as_bytes().to_vec();
+        bytes.resize((s.len() + 1).div_ceil(4) * 4, 0);
+        bytes
+    }
+
+    fn message(address: &str, volume: f32) -> Vec<u8> {
+        [string(address), string(",f"), volume.to_bits().to_be_bytes().to_vec()].concat()
+    }
+
+    fn bundle(elements: &[Vec<u8>]) -> Vec<u8> {
+        let mut packet = [string("#bundle"), vec![0; 8]].concat();
+        for element in elements {
+            packet.extend((element.len() as i32).to_be_bytes());
+            packet.extend(element);
+        }
+        packet
+    }
+
+    #[test]
+    fn decodes_messages_and_bundles() {
+        let messages = decode(&message("/serenit/master", 0.5)).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(
+            to_command(&messages[0]),
+            Some(Command::Master { volume }) if volume == 0.5
+        ));
+        let packet = bundle(&[message("/serenit/master", 0.5), bundle(&[message("/a", 1.0)])]);
+        let messages = decode(&packet).unwrap();
+        let addresses: Vec<&str> = messages.iter().map(|m| m.address.as_str()).collect();
+        assert_eq!(addresses, ["/serenit/master", "/a"]);
+    }
+
+    #[test]
+    fn rejects_truncated_packets() {
+        let packet = message("/serenit/master", 0.5);
+        assert!(decode(&packet[..packet.len() - 2]).is_err());
+        assert!(decode(&string("/serenit/master")[..8]).is_err());
+        let packet = bundle(&[message("/serenit/master", 0.5)]);
+        assert!(decode(&packet[..packet.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        let mut packet = [string("#bundle"), vec![0; 8]].concat();
+        packet.extend((-1i32).to_be_bytes());
+        assert!(decode(&packet).is_err());
+
+        let mut packet = [string("#bundle"), vec![0; 8]].concat();
+        packet.extend(i32::MAX.to_be_bytes());
+        assert!(decode(&packet).is_err());
+
+        for size in [-4i32, i32::MAX] {
+            let packet = [string("/a"), string(",b"), size.to_be_bytes().to_vec()].concat();
+            assert!(decode(&packet).is_err());
+        }
+    }
+
+    #[test]
+    fn limits_the_bundle_nesting() {
+        let nested = |depth: usize| {
+            (0..depth).fold(message("/serenit/master", 0.5), |packet, _| bundle(&[packet]))
+        };
+        assert_eq!(decode(&nested(MAX_BUNDLE_DEPTH)).unwrap().len(), 1);
+        assert!(decode(&nested(MAX_BUNDLE_DEPTH + 1)).is_err());
+        // As many as a datagram can hold
+        assert!(decode(&nested(4000)).is_err());
+    }
+}
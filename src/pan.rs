@@ -0,0 +1,103 @@
+use rodio::{Sample, Source};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Pan shared between the user interface and the audio thread, -1 is left and 1 right
+#[derive(Clone, Default)]
+pub struct Pan(Arc<AtomicU32>);
+
+impl Pan {
+    pub fn new(pan: f32) -> Self {
+        let p = Pan::default();
+        p.set(pan);
+        p
+    }
+
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, pan: f32) {
+        self.0.store(pan.clamp(-1.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Balances a source between the left and right channels, mono sources become stereo
+pub struct Panned<S: Iterator> {
+    input: S,
+    pan: Pan,
+    // Mono sample to repeat on the right channel
+    pending: Option<S::Item>,
+    // Channel of the next sample, 0 is left
+    channel: u16,
+}
+
+impl<S> Panned<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    pub fn new(input: S, pan: Pan) -> Self {
+        Panned {
+            input,
+            pan,
+            pending: None,
+            channel: 0,
+        }
+    }
+
+    fn gain(&self, channel: u16) -> f32 {
+        let pan = self.pan.get();
+        match channel {
+            0 => (1.0 - pan).min(1.0),
+            1 => (1.0 + pan).min(1.0),
+            _ => 1.0,
+        }
+    }
+}
+
+impl<S> Iterator for Panned<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<S::Item> {
+        if let Some(sample) = self.pending.take() {
+            return Some(sample.amplify(self.gain(1)));
+        }
+        let sample = self.input.next()?;
+        if self.input.channels() == 1 {
+            self.pending = Some(sample);
+            return Some(sample.amplify(self.gain(0)));
+        }
+        let channel = self.channel;
+        self.channel = (self.channel + 1) % self.input.channels();
+        Some(sample.amplify(self.gain(channel)))
+    }
+}
+
+impl<S> Source for Panned<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        let len = self.input.current_frame_len()?;
+        Some(if self.input.channels() == 1 { len * 2 } else { len })
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels().max(2)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
@@ -1,18 +1,29 @@
-use rodio::source::Source;
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
+use rodio::cpal::traits::HostTrait;
+use rodio::source::{Source, Zero};
+use rodio::{Decoder, Device, DeviceTrait, OutputStream, OutputStreamHandle, Sink};
+
+use crate::meter::{Level, Meter, Metered};
+use crate::pan::{Pan, Panned};
 
 use std::fs::File;
 use std::io::BufReader;
 
 pub struct SinkHandle {
-    _stream: OutputStream,
-    _stream_handle: OutputStreamHandle,
-    sink: Sink
+    // None for silent sinks
+    _stream: Option<(OutputStream, OutputStreamHandle)>,
+    sink: Sink,
+    pan: Pan,
+    meter: Meter,
 }
 
 impl SinkHandle {
-    pub fn new() -> SinkHandle {
-        let (stream, stream_handle) = match OutputStream::try_default() {
+    /// A sink playing on the given output, the default one when None
+    pub fn new(device: Option<&Device>) -> SinkHandle {
+        let stream = match device {
+            Some(device) => OutputStream::try_from_device(device),
+            None => OutputStream::try_default(),
+        };
+        let (stream, stream_handle) = match stream {
             Ok((stream, handle)) => (stream, handle),
             Err(_) => panic!("Failed to create stream"),
         };
@@ -21,9 +32,21 @@ impl SinkHandle {
             Err(_) => panic!("Failed to create sink"),
         };
         SinkHandle {
-            _stream: stream,
-            _stream_handle: stream_handle,
-            sink
+            _stream: Some((stream, stream_handle)),
+            sink,
+            pan: Pan::default(),
+            meter: Meter::default(),
+        }
+    }
+
+    /// A sink that is not connected to any output, used to mirror a remote player
+    pub fn silent() -> SinkHandle {
+        let (sink, _) = Sink::new_idle();
+        SinkHandle {
+            _stream: None,
+            sink,
+            pan: Pan::default(),
+            meter: Meter::default(),
         }
     }
 
@@ -35,16 +58,41 @@ impl SinkHandle {
         self.sink.is_paused()
     }
 
+    pub fn is_silent(&self) -> bool {
+        self._stream.is_none()
+    }
+
+    /// Levels of the playing source before the sink volume
+    pub fn level(&self) -> Level {
+        if self.is_paused() || !self.is_playing() {
+            return Level::default();
+        }
+        self.meter.level()
+    }
+
+    /// Waveform of the playing source before the sink volume, empty when nothing plays
+    pub fn wave(&self) -> Vec<f32> {
+        if self.is_paused() || !self.is_playing() {
+            return vec![];
+        }
+        self.meter.wave()
+    }
 
-    pub fn set_source(&mut self, source: &str) {
+
+    pub fn set_source(&mut self, source: &str) -> Result<(), String> {
         self.clear_if_playing();
-        self.add_to_queue(source);
+        self.add_to_queue(source)
     }
 
     pub fn set_volume(&self, volume: f32) {
         self.sink.set_volume(volume);
     }
 
+    /// Applies right away to the playing source, -1 is left and 1 right
+    pub fn set_pan(&self, pan: f32) {
+        self.pan.set(pan);
+    }
+
     pub fn play(&mut self) {
         self.sink.play();
     }
@@ -54,14 +102,31 @@ impl SinkHandle {
     }
 
     pub fn stop(&mut self) {
+        self.meter.reset();
+        if self._stream.is_none() {
+            // Nothing consumes an idle sink so clearing it would block, start from a new one
+            let (sink, _) = Sink::new_idle();
+            sink.set_volume(self.sink.volume());
+            if self.sink.is_paused() {
+                sink.pause();
+            }
+            self.sink = sink;
+            return;
+        }
         self.sink.stop();
         self.sink.clear();
     }
 
-    fn add_to_queue(&mut self, source: &str) {
-        let file: BufReader<File> = BufReader::new(File::open(source).unwrap());
-        let buffer = Decoder::new(file).unwrap();
-        self.sink.append(buffer.repeat_infinite());
+    fn add_to_queue(&mut self, source: &str) -> Result<(), String> {
+        if self._stream.is_none() {
+            self.sink.append(Zero::<f32>::new(2, 44100));
+            return Ok(());
+        }
+        let file = File::open(source).map_err(|e| format!("{}: {}", source, e))?;
+        let buffer = Decoder::new(BufReader::new(file)).map_err(|e| format!("{}: {}", source, e))?;
+        let source = Panned::new(buffer.repeat_infinite().convert_samples(), self.pan.clone());
+        self.sink.append(Metered::new(source, self.meter.clone()));
+        Ok(())
     }
 
     fn clear_if_playing(&mut self) {
@@ -70,3 +135,16 @@ impl SinkHandle {
         }
     }
 }
+
+/// The audio output of that name, the error listing the available ones
+pub fn output_device(name: &str) -> Result<Device, String> {
+    let devices: Vec<Device> = rodio::cpal::default_host()
+        .output_devices()
+        .map_err(|e| e.to_string())?
+        .collect();
+    let names: Vec<String> = devices.iter().filter_map(|d| d.name().ok()).collect();
+    devices
+        .into_iter()
+        .find(|d| d.name().is_ok_and(|n| n == name))
+        .ok_or_else(|| format!("unknown output device '{}', available: {}", name, names.join(", ")))
+}
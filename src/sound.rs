@@ -4,6 +4,9 @@ pub struct Sound {
     path: String,
     category: String,
     volume: f32,
+    muted: bool,
+    // -1 is left, 1 right
+    pan: f32,
 }
 
 impl Sound {
@@ -13,6 +16,8 @@ impl Sound {
             path: path.to_string(),
             category: category.to_string(),
             volume,
+            muted: false,
+            pan: 0.0,
         }
     }
 
@@ -35,4 +40,20 @@ impl Sound {
     pub fn set_volume(&mut self, volume: f32) {
         self.volume = volume;
     }
+
+    pub fn muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    pub fn pan(&self) -> f32 {
+        self.pan
+    }
+
+    pub fn set_pan(&mut self, pan: f32) {
+        self.pan = pan.clamp(-1.0, 1.0);
+    }
 }
@@ -1,36 +1,159 @@
+use crate::control::{Command, Response, Status};
+use crate::dirs::Dirs;
+use crate::files::write_atomic;
+use crate::import::Manifest;
+use crate::meter::{mix_waves, Level};
 use crate::sink_handle::SinkHandle;
 use crate::sound::Sound;
+use rodio::Device;
 use cli_log::*;
-use homedir::my_home;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::Read;
 use thiserror::Error;
-use std::path::Path;
-
-const MAX_SOUNDS: usize = 8;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_MAX_LAYERS: usize = 8;
+// Highest max_layers, also the number of sinks of the silent managers to mirror any daemon
+pub const MAX_LAYERS: usize = 32;
+// The session is written once the mix has not changed for this long
+const AUTOSAVE_DELAY: Duration = Duration::from_secs(1);
+pub const DEFAULT_SOUND_ROOT: &str = "./sounds";
+// Extensions of the files rodio is built to decode
+pub const SOUND_EXTENSIONS: [&str; 3] = ["mp3", "wav", "flac"];
 
 pub struct SoundManager {
     available_sounds: Vec<Sound>,
     sinks: Vec<SinkHandle>,
     playing_sounds: HashMap<String, usize>,
-    config_path: String,
+    dirs: Dirs,
+    config_path: PathBuf,
+    sound_root: String,
+    volumes: Volumes,
     categories: Vec<String>,
+    master_volume: f32,
+    current_preset: Option<String>,
+    // Levels and waveform sent by the daemon, for the silent managers mirroring it
+    mirrored_levels: Vec<Level>,
+    mirrored_wave: Vec<f32>,
+    // Play time before the last resume, and when it resumed (None while paused)
+    played: Duration,
+    resumed: Option<Instant>,
+    // Last change of the mix not written to the session yet
+    changed: Option<Instant>,
+}
+
+/// Startup options, usually filled from the command line
+pub struct SoundManagerOptions {
+    /// Where the presets, the library and the session are
+    pub dirs: Dirs,
+    /// Saved mix, defaults to the `sounds.json` of the config folder
+    pub config_path: Option<PathBuf>,
+    /// Folder containing the category folders
+    pub sound_root: String,
+    /// Keeps the one of the last session when None
+    pub master_volume: Option<f32>,
+    /// Preset to load instead of the last session
+    pub preset: Option<String>,
+    /// Preset played when there is no session, before the saved mix
+    pub default_preset: Option<String>,
+    /// Most sounds played together
+    pub max_layers: usize,
+    /// Starting volumes of the sounds
+    pub volumes: Volumes,
+    /// Audio output, the system default when None
+    pub output_device: Option<Device>,
+    /// Silent managers play nothing and start empty, they mirror a daemon
+    pub silent: bool,
+    /// Keeps a session left paused paused, only where it can be resumed
+    pub restore_pause: bool,
+}
+
+impl Default for SoundManagerOptions {
+    fn default() -> Self {
+        SoundManagerOptions {
+            dirs: Dirs::from_env(),
+            config_path: None,
+            sound_root: DEFAULT_SOUND_ROOT.to_string(),
+            master_volume: None,
+            preset: None,
+            default_preset: None,
+            max_layers: DEFAULT_MAX_LAYERS,
+            volumes: Volumes::default(),
+            output_device: None,
+            silent: false,
+            restore_pause: false,
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize)]
+/// Starting volumes of the sounds, by category or sound name
+#[derive(Debug, Clone)]
+pub struct Volumes(HashMap<String, f32>);
+
+impl Default for Volumes {
+    fn default() -> Self {
+        // Binaural beats and noises are loud
+        Volumes(HashMap::from([
+            ("default".to_string(), 0.5),
+            ("binaural".to_string(), 0.2),
+            ("noise".to_string(), 0.2),
+        ]))
+    }
+}
+
+impl Volumes {
+    pub fn extend(&mut self, volumes: &HashMap<String, f32>) {
+        self.0.extend(volumes.iter().map(|(name, volume)| (name.clone(), *volume)));
+    }
+
+    /// The volume of the sound name (without extension), else of its category, else the default
+    pub fn get(&self, category: &str, name: &str) -> f32 {
+        [name, category, "default"]
+            .iter()
+            .find_map(|key| self.0.get(*key))
+            .copied()
+            .unwrap_or(0.5)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SoundData {
     pub source: String,
     pub volume: f32,
+    #[serde(default)]
+    pub muted: bool,
+    /// -1 is left, 1 right
+    #[serde(default)]
+    pub pan: f32,
 }
 
+/// Where the mix was left, restored on the next start
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub paused: bool,
+    pub master_volume: f32,
+    #[serde(default)]
+    pub preset: Option<String>,
+    pub layers: Vec<SoundData>,
+}
+
+#[derive(Debug, Error)]
 pub enum SoundManagerError {
+    #[error("No available sound slot, the last one was replaced")]
     NoAvailableSound,
+    #[error("Sound already playing")]
     AlreadyPlaying,
+    #[error("Sound already stopped")]
     AlreadyStopped,
+    #[error("Sound does not exist")]
     SoundDoesNotExists,
-    OtherError,
+    #[error("Cannot play {0}")]
+    CannotPlay(String),
+    #[error("Error: {0}")]
+    OtherError(String),
 }
 
 #[derive(Debug, Error)]
@@ -42,24 +165,54 @@ pub enum FileError {
 }
 
 impl SoundManager {
-    pub fn new() -> Self {
+    /// Fails only when the preset asked for cannot be loaded
+    pub fn new(options: SoundManagerOptions) -> Result<Self, FileError> {
         let mut sinks = vec![];
-        for _i in 0..MAX_SOUNDS {
-            sinks.push(SinkHandle::new());
+        if options.silent {
+            sinks.resize_with(MAX_LAYERS, SinkHandle::silent);
+        } else {
+            let device = options.output_device.as_ref();
+            sinks.resize_with(options.max_layers, || SinkHandle::new(device));
         }
         let mut sm = SoundManager {
             sinks,
             available_sounds: vec![],
             playing_sounds: HashMap::new(),
-            config_path: "".to_string(),
+            config_path: options.config_path.unwrap_or_else(|| options.dirs.saved_mix()),
+            dirs: options.dirs,
+            sound_root: options.sound_root,
+            volumes: options.volumes,
             categories: vec![],
+            master_volume: options.master_volume.unwrap_or(1.0).clamp(0.0, 1.0),
+            current_preset: None,
+            mirrored_levels: vec![],
+            mirrored_wave: vec![],
+            played: Duration::ZERO,
+            resumed: Some(Instant::now()),
+            changed: None,
         };
         sm.load_available_sounds();
-        sm.load_presets().unwrap_or_else(|err| {
-            warn!("No presets found, {}. Loading default demo",err);
-            sm.demo();
-        });
+        if options.silent {
+            return Ok(sm);
+        }
+        if let Some(preset) = &options.preset {
+            sm.load_preset(preset)?;
+            return Ok(sm);
+        }
         sm
+            .load_session(options.master_volume.is_none(), options.restore_pause)
+            .or_else(|e| {
+                info!("No session to restore, {}", e);
+                match &options.default_preset {
+                    Some(preset) => sm.load_preset(preset),
+                    None => sm.load_presets(),
+                }
+            })
+            .unwrap_or_else(|err| {
+                warn!("No presets found, {}. Loading default demo",err);
+                sm.demo();
+            });
+        Ok(sm)
     }
 
     //===== Getters
@@ -75,32 +228,30 @@ impl SoundManager {
         &self.categories
     }
 
-    pub fn is_sound_playing(&self, path: &str) -> bool {
-        if !self.playing_sounds.contains_key(path) {
-            return false;
-        }
-        let sink_index = self.playing_sounds.get(path).unwrap();
-        self.sinks[*sink_index].is_playing()
+    pub fn master_volume(&self) -> f32 {
+        self.master_volume
     }
 
-    pub fn get_sound_path_by_index(&self, index: usize) -> &str {
-        self.available_sounds[index].path()
+    pub fn config_path(&self) -> &Path {
+        &self.config_path
     }
 
-    pub fn get_sound_path_by_index_and_category(&self, index: usize, category_index : Option<usize>) -> &str {
-        let cat_index = self.available_sounds
-        .iter()
-        .enumerate()
-        .find(|(_,s)| {
-            match category_index {
-                Some(i) => s.category() == self.categories[i],
-                None => true
-            }
-        });
+    pub fn dirs(&self) -> &Dirs {
+        &self.dirs
+    }
+
+    pub fn current_preset(&self) -> Option<&str> {
+        self.current_preset.as_deref()
+    }
+
+    pub fn presets_dir(&self) -> PathBuf {
+        self.dirs.presets()
+    }
 
-        match cat_index {
-            Some((i,_s)) => self.available_sounds[i+index].path(),
-            None => ""
+    pub fn is_sound_playing(&self, path: &str) -> bool {
+        match self.playing_sounds.get(path) {
+            Some(sink_index) => self.sinks[*sink_index].is_playing(),
+            None => false,
         }
     }
 
@@ -108,14 +259,24 @@ impl SoundManager {
         self.available_sounds.iter().find(|s| s.path() == path)
     }
 
+    /// Finds a sound by path, by name with or without extension, or by file name
+    /// without extension, the one of an imported sound named otherwise
+    pub fn find_sound(&self, key: &str) -> Option<&Sound> {
+        let stem_is = |path: &str| Path::new(path).file_stem().is_some_and(|stem| stem == key);
+        self.get_sound_by_path(key).or_else(|| {
+            self.available_sounds
+                .iter()
+                .find(|s| s.name() == key || stem_is(s.name()) || stem_is(s.path()))
+        })
+    }
+
     //===== Actions
     pub fn toggle_sound(&mut self, path: &str) -> Result<(), SoundManagerError> {
-        let res = if self.is_sound_playing(path) {
+        if self.is_sound_playing(path) {
             self.remove_sound(path)
         } else {
             self.add_sound(path)
-        };
-        res
+        }
     }
 
     fn add_sound(&mut self, path: &str) -> Result<(), SoundManagerError> {
@@ -130,18 +291,17 @@ impl SoundManager {
         let sink_index = match self.find_available() {
             Some(i) => i,
             None => {
-                self.overwrite_last(path, volume);
+                self.overwrite_last(path, volume)?;
                 return Err(SoundManagerError::NoAvailableSound);
             }
         };
 
         // Set the source and volume of the found sink
-        self.set_sink_source(sink_index, path, volume);
-        Ok(())
+        self.set_sink_source(sink_index, path, volume)
     }
 
     fn remove_sound(&mut self, path: &str) -> Result<(), SoundManagerError> {
-        if let Some(_) = self.get_sound_by_path(path) {
+        if self.get_sound_by_path(path).is_some() {
             let path = &path.to_string();
             match self.playing_sounds.get(path) {
                 Some(i) => {
@@ -169,39 +329,359 @@ impl SoundManager {
         self.sinks.iter().all(|sink| sink.is_paused())
     }
 
-    pub fn adjust_sound_volume(&mut self, path: &str, volume_offset: f32) {
-        self.adjust_volume(path, volume_offset, false);
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused {
+            self.pause_all();
+        } else {
+            self.play_all();
+        }
+    }
+
+    pub fn set_sound_volume(&mut self, path: &str, volume: f32) {
+        if let Some(sound) = self.get_sound_by_path(path) {
+            let offset = volume - sound.volume();
+            self.adjust_volume(path, offset, false);
+        }
     }
 
-    pub fn adjust_master_volume(&mut self, volume_offset: f32){
-        let sounds:Vec<String> = self.playing_sounds.keys().map(|path| path.clone()).collect();
-        sounds.iter().for_each(|path| {
-            self.adjust_volume(path, volume_offset, true);
-        });
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.master_volume = volume.clamp(0.0, 1.0);
+        for (path, i) in self.playing_sounds.iter() {
+            if let Some(sound) = self.available_sounds.iter().find(|s| s.path() == path) {
+                self.sinks[*i].set_volume(sink_volume(sound, self.master_volume));
+            }
+        }
+    }
+
+    /// A muted sound keeps its volume and its sink
+    pub fn set_sound_muted(&mut self, path: &str, muted: bool) {
+        if let Some(sound) = self.available_sounds.iter_mut().find(|s| s.path() == path) {
+            sound.set_muted(muted);
+            if let Some(i) = self.playing_sounds.get(path) {
+                self.sinks[*i].set_volume(sink_volume(sound, self.master_volume));
+            }
+        }
     }
 
-    fn overwrite_last(&mut self, source: &String, volume: f32) {
+    pub fn set_sound_pan(&mut self, path: &str, pan: f32) {
+        if let Some(sound) = self.available_sounds.iter_mut().find(|s| s.path() == path) {
+            sound.set_pan(pan);
+            if let Some(i) = self.playing_sounds.get(path) {
+                self.sinks[*i].set_pan(sound.pan());
+            }
+        }
+    }
+
+    /// Stops every sound and plays the given preset instead
+    pub fn load_preset(&mut self, preset: &str) -> Result<(), FileError> {
+        self.load_preset_file(&self.dirs.preset(preset))
+    }
+
+    fn load_preset_file(&mut self, path: &Path) -> Result<(), FileError> {
+        let config = read_preset(path)?;
+        self.stop_all();
+        self.apply_preset(&config);
+        self.current_preset = Some(preset_name(path));
+        Ok(())
+    }
+
+    /// Saves the mix as a named preset
+    pub fn save_preset(&mut self, preset: &str) -> Result<(), FileError> {
+        self.save_preset_file(&self.dirs.preset(preset))
+    }
+
+    fn save_preset_file(&mut self, path: &Path) -> Result<(), FileError> {
+        self.save_to(path)?;
+        self.current_preset = Some(preset_name(path));
+        Ok(())
+    }
+
+    pub fn list_presets(&self) -> Vec<String> {
+        self.dirs.list_presets()
+    }
+
+    fn overwrite_last(&mut self, source: &String, volume: f32) -> Result<(), SoundManagerError> {
         let mut path = "".to_string();
-        let mut sink_index = MAX_SOUNDS;
+        let mut sink_index = self.sinks.len();
         self.playing_sounds.keys().for_each(|p| {
-            let index = self.playing_sounds.get(p).unwrap().clone();
+            let index = *self.playing_sounds.get(p).unwrap();
             if index == self.sinks.len() - 1 {
                 path = p.clone();
                 sink_index = index;
             }
         });
         self.playing_sounds.remove(&path);
-        self.set_sink_source(sink_index, source, volume);
+        self.set_sink_source(sink_index, source, volume)
     }
 
     pub fn save(&mut self) -> Result<(), FileError> {
-        if self.config_path.is_empty() {
+        if self.config_path.as_os_str().is_empty() {
             return Err(FileError::IoError(std::io::Error::new(std::io::ErrorKind::NotFound, "No config path found")));
         }
-        self.save_to(self.config_path.clone())
+        self.save_to(&self.config_path)
+    }
+
+    //===== Remote control
+    pub fn execute(&mut self, command: &Command) -> Response {
+        let mut data = None;
+        let res = match command {
+            Command::Toggle { path } => match self.find_sound(path).map(|s| s.path().to_string()) {
+                Some(path) => match self.toggle_sound(&path) {
+                    Err(SoundManagerError::NoAvailableSound) | Ok(()) => Ok(()),
+                    Err(e) => Err(e.to_string()),
+                },
+                None => Err(SoundManagerError::SoundDoesNotExists.to_string()),
+            },
+            Command::Volume { path, volume } => match self.find_sound(path).map(|s| s.path().to_string()) {
+                Some(path) => {
+                    self.set_sound_volume(&path, *volume);
+                    Ok(())
+                }
+                None => Err(SoundManagerError::SoundDoesNotExists.to_string()),
+            },
+            Command::Mute { path, muted } => match self.find_sound(path).map(|s| s.path().to_string()) {
+                Some(path) => {
+                    self.set_sound_muted(&path, *muted);
+                    Ok(())
+                }
+                None => Err(SoundManagerError::SoundDoesNotExists.to_string()),
+            },
+            Command::Pan { path, pan } => match self.find_sound(path).map(|s| s.path().to_string()) {
+                Some(path) => {
+                    self.set_sound_pan(&path, *pan);
+                    Ok(())
+                }
+                None => Err(SoundManagerError::SoundDoesNotExists.to_string()),
+            },
+            Command::Master { volume } => {
+                self.set_master_volume(*volume);
+                Ok(())
+            }
+            // Only the presets of the presets folder can be reached from outside
+            Command::Load { preset } => self.dirs.named_preset(preset).and_then(|path| {
+                self.load_preset_file(&path).map_err(|e| e.to_string())
+            }),
+            Command::Pause => {
+                self.set_paused(true);
+                Ok(())
+            }
+            Command::Play => {
+                self.set_paused(false);
+                Ok(())
+            }
+            Command::TogglePause => {
+                self.toggle_pause_play();
+                Ok(())
+            }
+            Command::Save { preset: None } => self.save().map_err(|e| e.to_string()),
+            Command::Save { preset: Some(preset) } => {
+                self.dirs.named_preset(preset).and_then(|path| {
+                    self.save_preset_file(&path).map_err(|e| e.to_string())
+                })
+            }
+            Command::Status | Command::Quit => Ok(()),
+            Command::Sounds => {
+                data = Some(serde_json::Value::Array(
+                    self.available_sounds
+                        .iter()
+                        .map(|s| {
+                            serde_json::json!({
+                                "name": s.name(),
+                                "path": s.path(),
+                                "category": s.category(),
+                                "volume": s.volume(),
+                                "muted": s.muted(),
+                                "pan": s.pan(),
+                                "playing": self.is_sound_playing(s.path()),
+                            })
+                        })
+                        .collect(),
+                ));
+                Ok(())
+            }
+            Command::Categories => {
+                data = Some(serde_json::json!(self.categories));
+                Ok(())
+            }
+            Command::Presets => {
+                data = Some(serde_json::json!(self.list_presets()));
+                Ok(())
+            }
+            Command::Rescan => {
+                data = Some(serde_json::json!(self.rescan()));
+                Ok(())
+            }
+        };
+        if command.changes_mix() && res.is_ok() {
+            self.changed = Some(Instant::now());
+        }
+        Response {
+            ok: res.is_ok(),
+            error: res.err(),
+            status: self.status(),
+            data,
+        }
+    }
+
+    //===== Session
+    /// Writes the session once the mix has settled, to be called regularly by the owner
+    pub fn autosave(&mut self) {
+        if self.changed.is_some_and(|t| t.elapsed() >= AUTOSAVE_DELAY) {
+            self.save_session();
+        }
+    }
+
+    /// Writes the session now if the mix changed, when exiting
+    pub fn save_session(&mut self) {
+        // Mirrors leave the session to the daemon
+        if self.changed.take().is_none() || self.is_silent() {
+            return;
+        }
+        let session = Session {
+            paused: self.is_paused(),
+            master_volume: self.master_volume,
+            preset: self.current_preset.clone(),
+            layers: self.layers(),
+        };
+        let path = self.dirs.session();
+        let res = serde_json::to_string(&session)
+            .map_err(FileError::from)
+            .and_then(|json| Ok(write_atomic(&path, json.as_bytes())?));
+        match res {
+            Ok(()) => debug!("Session saved to {}", path.display()),
+            Err(e) => warn!("Cannot save the session {}: {}", path.display(), e),
+        }
+    }
+
+    // Restores the last session, and its master volume unless another one was asked for
+    fn load_session(&mut self, restore_volume: bool, restore_pause: bool) -> Result<(), FileError> {
+        let text = std::fs::read_to_string(self.dirs.session())?;
+        let session: Session = serde_json::from_str(&text)?;
+        if restore_volume {
+            self.master_volume = session.master_volume.clamp(0.0, 1.0);
+        }
+        self.apply_preset(&session.layers);
+        if session.paused && restore_pause {
+            self.pause_all();
+        }
+        self.current_preset = session.preset;
+        Ok(())
+    }
+
+    pub fn status(&self) -> Status {
+        Status {
+            paused: self.is_paused(),
+            master_volume: self.master_volume,
+            preset: self.current_preset.clone(),
+            layers: self.layers(),
+            levels: self.levels(),
+            wave: self.wave(),
+            play_time: self.play_time().as_secs(),
+        }
+    }
+
+    /// How long the mix has been playing, pauses excluded
+    pub fn play_time(&self) -> Duration {
+        self.played + self.resumed.map_or(Duration::ZERO, |t| t.elapsed())
+    }
+
+    /// Playing sounds, in sink order
+    pub fn layers(&self) -> Vec<SoundData> {
+        let mut layers: Vec<(usize, SoundData)> = self
+            .playing_sounds
+            .iter()
+            .filter_map(|(path, i)| {
+                self.get_sound_by_path(path).map(|sound| {
+                    (*i, SoundData {
+                        source: path.clone(),
+                        volume: sound.volume(),
+                        muted: sound.muted(),
+                        pan: sound.pan(),
+                    })
+                })
+            })
+            .collect();
+        layers.sort_by_key(|(i, _)| *i);
+        layers.into_iter().map(|(_, layer)| layer).collect()
+    }
+
+    /// Levels of the layers after their volume, in sink order like `layers()`
+    pub fn levels(&self) -> Vec<Level> {
+        if self.is_silent() {
+            return self.mirrored_levels.clone();
+        }
+        let mut levels: Vec<(usize, Level)> = self
+            .playing_sounds
+            .iter()
+            .filter_map(|(path, i)| {
+                self.get_sound_by_path(path).map(|sound| {
+                    let volume = sink_volume(sound, self.master_volume);
+                    (*i, self.sinks[*i].level().scaled(volume))
+                })
+            })
+            .collect();
+        levels.sort_by_key(|(i, _)| *i);
+        levels.into_iter().map(|(_, level)| level).collect()
+    }
+
+    /// Waveform of the mix, empty when nothing plays
+    pub fn wave(&self) -> Vec<f32> {
+        if self.is_silent() {
+            return self.mirrored_wave.clone();
+        }
+        let waves: Vec<(Vec<f32>, f32)> = self
+            .playing_sounds
+            .iter()
+            .filter_map(|(path, i)| {
+                let sound = self.get_sound_by_path(path)?;
+                let wave = self.sinks[*i].wave();
+                (!wave.is_empty()).then(|| (wave, sink_volume(sound, self.master_volume)))
+            })
+            .collect();
+        if waves.is_empty() {
+            return vec![];
+        }
+        mix_waves(&waves)
+    }
+
+    /// Makes this manager mirror the given status
+    pub fn apply_status(&mut self, status: &Status) {
+        let stopped: Vec<String> = self
+            .playing_sounds
+            .keys()
+            .filter(|path| !status.layers.iter().any(|l| &l.source == *path))
+            .cloned()
+            .collect();
+        stopped.iter().for_each(|path| {
+            let _ = self.remove_sound(path);
+        });
+        status.layers.iter().for_each(|layer| {
+            if let Some(sound) = self.available_sounds.iter_mut().find(|s| s.path() == layer.source) {
+                sound.set_volume(layer.volume);
+                sound.set_muted(layer.muted);
+                sound.set_pan(layer.pan);
+            }
+            if !self.is_sound_playing(&layer.source) {
+                let _ = self.add_sound(&layer.source);
+            }
+        });
+        self.set_master_volume(status.master_volume);
+        if status.paused != self.is_paused() {
+            self.set_paused(status.paused);
+        }
+        self.current_preset = status.preset.clone();
+        self.mirrored_levels = status.levels.clone();
+        self.mirrored_wave = status.wave.clone();
+        self.played = Duration::from_secs(status.play_time);
+        self.resumed = None;
     }
 
     //===== Misc
+    // Silent managers mirror a daemon
+    fn is_silent(&self) -> bool {
+        self.sinks.iter().all(|sink| sink.is_silent())
+    }
+
     fn find_available(&self) -> Option<usize> {
         self.sinks
             .iter()
@@ -214,12 +694,23 @@ impl SoundManager {
         self.sinks.iter_mut().for_each(|sink| {
             sink.pause();
         });
+        if let Some(resumed) = self.resumed.take() {
+            self.played += resumed.elapsed();
+        }
     }
 
     fn play_all(&mut self) {
         self.sinks.iter_mut().for_each(|sink| {
             sink.play();
         });
+        self.resumed.get_or_insert_with(Instant::now);
+    }
+
+    fn stop_all(&mut self) {
+        self.sinks.iter_mut().for_each(|sink| {
+            sink.stop();
+        });
+        self.playing_sounds.clear();
     }
 
     fn demo(&mut self) {
@@ -248,178 +739,257 @@ impl SoundManager {
 
             // Find the corresponding sink and update its volume
             if let Some(i) = self.playing_sounds.get(sound.path()) {
-                self.sinks[*i].set_volume(new_volume);
+                self.sinks[*i].set_volume(sink_volume(sound, self.master_volume));
             }
         }
     }
 
-    fn set_sink_source(&mut self, sink_index: usize, path: &String, volume: f32) {
+    fn set_sink_source(&mut self, sink_index: usize, path: &String, volume: f32) -> Result<(), SoundManagerError> {
         info!("Playing sound {} to sink {}", path, sink_index);
-        self.playing_sounds.insert(path.clone(), sink_index);
-
+        let (muted, pan) = self
+            .get_sound_by_path(path)
+            .map(|s| (s.muted(), s.pan()))
+            .unwrap_or((false, 0.0));
         let sink = &mut self.sinks[sink_index];
-        sink.set_volume(volume);
-        sink.set_source(path);
+        sink.set_volume(if muted { 0.0 } else { volume * self.master_volume });
+        sink.set_pan(pan);
+        if let Err(e) = sink.set_source(path) {
+            warn!("Cannot play {}", e);
+            return Err(SoundManagerError::CannotPlay(e));
+        }
         sink.play();
+        self.playing_sounds.insert(path.clone(), sink_index);
+        Ok(())
     }
 
     fn load_presets(&mut self) -> Result<(), FileError> {
-        let home_dir = my_home().map_err(|e| FileError::IoError(std::io::Error::new(std::io::ErrorKind::Unsupported, e)))?;
-        let path = home_dir.ok_or_else(|| FileError::IoError(std::io::Error::new(std::io::ErrorKind::NotFound, "Home directory not found")))?
-        .to_str()
-        .unwrap()
-        .to_string() + "/.config/serenIT/sounds.json";
-
-        self.config_path = path.clone();
-    
-        let res = self.read_from_file(&path);
-        res
+        let path = self.config_path.clone();
+        self.read_from_file(&path)
     }
 
-    fn save_to(&self, path: String) -> Result<(), FileError> {
-        // Create/open the file
-        let file_path = Path::new(&path);
-        let parent_dir = file_path.parent().ok_or(FileError::IoError(std::io::Error::new(std::io::ErrorKind::NotFound, "No parent directory found")))?;
-        std::fs::create_dir_all(parent_dir)?;
-        let mut f = File::create(path)?;
-
+    fn save_to(&self, path: &Path) -> Result<(), FileError> {
         // Serialize the struct
-        let mut config:Vec<SoundData> = vec![];
-        self.playing_sounds
-            .keys()
-            .for_each(|path| {
-                if let Some(sound) = self.get_sound_by_path(path){
-                config.push(SoundData {
-                    source: path.clone(),
-                    volume: sound.volume(),
-                });
-            }
-            });
+        let config = self.layers();
         let serialized = serde_json::to_string(&config)?;
 
         info!("Saving to file: {}", serialized);
 
-        // Write to file
-        f.write_all(serialized.as_bytes())?;
+        // Never leaves a truncated file, the previous one is kept as a backup
+        write_atomic(path, serialized.as_bytes())?;
 
         Ok(())
     }
 
-    fn read_from_file(&mut self, path: &str) -> Result<(), FileError> {
-        let mut file = File::open(path)?;
-        let mut buff = String::new();
-        file.read_to_string(&mut buff)?;
-        let config: Vec<SoundData> = serde_json::from_str(&buff)?;
-        for (i, s) in config.iter().enumerate() {
-            if s.source.len() > 0 {
+    fn read_from_file(&mut self, path: &Path) -> Result<(), FileError> {
+        let config = read_preset(path)?;
+        self.apply_preset(&config);
+        Ok(())
+    }
+
+    fn apply_preset(&mut self, config: &[SoundData]) {
+        for (i, s) in config.iter().take(self.sinks.len()).enumerate() {
+            if !s.source.is_empty() {
                 info!("Loading from file: {}, with volume {}", s.source, s.volume);
-                self.available_sounds.iter_mut()
-                .find(|sound| sound.path() == s.source)
-                .map(|sound| sound.set_volume(s.volume));
+                if let Some(sound) = self.available_sounds.iter_mut().find(|sound| sound.path() == s.source) {
+                    sound.set_volume(s.volume);
+                    sound.set_muted(s.muted);
+                    sound.set_pan(s.pan);
+                }
 
-                self.set_sink_source(i, &s.source, s.volume);
+                let _ = self.set_sink_source(i, &s.source, s.volume);
+            }
+        }
+    }
+
+    /// Reads the sound folders again, keeping the volumes of the known sounds
+    /// and stopping the playing ones that were removed, whose paths are returned
+    pub fn rescan(&mut self) -> Vec<String> {
+        let sounds = load_available_sounds(&sound_folders(&self.sound_root, &self.dirs), &self.volumes);
+        let mut removed: Vec<String> = self
+            .playing_sounds
+            .keys()
+            .filter(|path| !sounds.iter().any(|s| s.path() == *path))
+            .cloned()
+            .collect();
+        removed.sort();
+        for path in &removed {
+            warn!("Playing sound {} was removed", path);
+            let _ = self.remove_sound(path);
+        }
+        if !removed.is_empty() {
+            self.changed = Some(Instant::now());
+        }
+        let mut known: HashMap<String, Sound> = self
+            .available_sounds
+            .drain(..)
+            .map(|s| (s.path().to_string(), s))
+            .collect();
+        self.available_sounds = sounds
+            .into_iter()
+            .map(|s| known.remove(s.path()).unwrap_or(s))
+            .collect();
+        self.update_categories();
+        info!("Rescanned the sounds, {} found", self.available_sounds.len());
+        removed
+    }
+
+    /// Changes the starting volumes, the sounds not playing take their new one
+    pub fn set_volumes(&mut self, volumes: Volumes) {
+        self.volumes = volumes;
+        for sound in &mut self.available_sounds {
+            if !self.playing_sounds.contains_key(sound.path()) {
+                let stem = Path::new(sound.path()).file_stem().unwrap_or_default();
+                sound.set_volume(self.volumes.get(sound.category(), &stem.to_string_lossy()));
             }
         }
-        Ok(())
     }
 
     fn load_available_sounds(&mut self) {
-        self.available_sounds.clear();
-        let params: Vec<&str> = vec![
-            // sounds/
-            "./sounds/animals/birds.mp3",
-            "./sounds/animals/cat-purring.mp3",
-            "./sounds/animals/crickets.mp3",
-            "./sounds/animals/crows.mp3",
-            "./sounds/animals/dog-barking.mp3",
-            "./sounds/animals/frog.mp3",
-            "./sounds/animals/horse-galopp.mp3",
-            "./sounds/animals/owl.mp3",
-            "./sounds/animals/seagulls.mp3",
-            "./sounds/animals/whale.mp3",
-            "./sounds/animals/wolf.mp3",
-            "./sounds/binaural/binaural-alpha.wav",
-            "./sounds/binaural/binaural-beta.wav",
-            "./sounds/binaural/binaural-delta.wav",
-            "./sounds/binaural/binaural-gamma.wav",
-            "./sounds/binaural/binaural-theta.wav",
-            "./sounds/nature/campfire.mp3",
-            "./sounds/nature/droplets.mp3",
-            "./sounds/nature/jungle.mp3",
-            "./sounds/nature/river.mp3",
-            "./sounds/nature/walk-in-snow.mp3",
-            "./sounds/nature/walk-on-leaves.mp3",
-            "./sounds/nature/waterfall.mp3",
-            "./sounds/nature/waves.mp3",
-            "./sounds/nature/wind.mp3",
-            "./sounds/nature/wind-in-trees.mp3",
-            "./sounds/noise/brown-noise.wav",
-            "./sounds/noise/pink-noise.wav",
-            "./sounds/noise/white-noise.wav",
-            "./sounds/places/airport.mp3",
-            "./sounds/places/cafe.mp3",
-            "./sounds/places/carousel.mp3",
-            "./sounds/places/church.mp3",
-            "./sounds/places/construction-site.mp3",
-            "./sounds/places/crowded-bar.mp3",
-            "./sounds/places/laboratory.mp3",
-            "./sounds/places/laundry-room.mp3",
-            "./sounds/places/night-village.mp3",
-            "./sounds/places/office.mp3",
-            "./sounds/places/subway-station.mp3",
-            "./sounds/places/supermarket.mp3",
-            "./sounds/places/temple.mp3",
-            "./sounds/places/underwater.mp3",
-            "./sounds/rain/heavy-rain.mp3",
-            "./sounds/rain/light-rain.mp3",
-            "./sounds/rain/rain-on-leaves.mp3",
-            "./sounds/rain/rain-on-tent.mp3",
-            "./sounds/rain/rain-on-umbrella.mp3",
-            "./sounds/rain/rain-on-window.mp3",
-            "./sounds/rain/thunder.mp3",
-            "./sounds/things/boiling-water.mp3",
-            "./sounds/things/bubbles.mp3",
-            "./sounds/things/ceiling-fan.mp3",
-            "./sounds/things/clock.mp3",
-            "./sounds/things/dryer.mp3",
-            "./sounds/things/keyboard.mp3",
-            "./sounds/things/morse-code.mp3",
-            "./sounds/things/paper.mp3",
-            "./sounds/things/slide-projector.mp3",
-            "./sounds/things/singing-bowl.mp3",
-            "./sounds/things/tuning-radio.mp3",
-            "./sounds/things/typewriter.mp3",
-            "./sounds/things/washing-machine.mp3",
-            "./sounds/things/wind-chimes.mp3",
-            "./sounds/transport/airplane.mp3",
-            "./sounds/transport/inside-a-train.mp3",
-            "./sounds/transport/rowing-boat.mp3",
-            "./sounds/transport/sailboat.mp3",
-            "./sounds/transport/submarine.mp3",
-            "./sounds/transport/train.mp3",
-            "./sounds/urban/ambulance-siren.mp3",
-            "./sounds/urban/busy-street.mp3",
-            "./sounds/urban/crowd.mp3",
-            "./sounds/urban/fireworks.mp3",
-            "./sounds/urban/highway.mp3",
-            "./sounds/urban/road.mp3",
-            "./sounds/urban/traffic.mp3",
-            "./sounds/alarm.mp3",
-        ];
-        params.iter().for_each(|path| {
-            let folders = path.split("/").collect::<Vec<&str>>();
-            let filename = folders[folders.len() - 1];
-            let category = folders[folders.len() - 2];
-            let volume = if filename.contains("binaural") || filename.contains("noise") {
-                0.2
-            } else {
-                0.5
-            };
-            self.available_sounds
-                .push(Sound::new(filename, path, category, volume));
-            if !self.categories.contains(&category.to_string()) {
-                self.categories.push(category.to_string());
+        let folders = sound_folders(&self.sound_root, &self.dirs);
+        self.available_sounds = load_available_sounds(&folders, &self.volumes);
+        self.update_categories();
+    }
+
+    fn update_categories(&mut self) {
+        self.categories.clear();
+        self.available_sounds.iter().for_each(|sound| {
+            if !self.categories.iter().any(|c| c == sound.category()) {
+                self.categories.push(sound.category().to_string());
             }
         });
     }
 }
+
+// Volume of the sink playing a sound
+fn sink_volume(sound: &Sound, master_volume: f32) -> f32 {
+    if sound.muted() {
+        0.0
+    } else {
+        sound.volume() * master_volume
+    }
+}
+
+/// Reads a preset file, a list of sources and their volume
+pub fn read_preset(path: &Path) -> Result<Vec<SoundData>, FileError> {
+    let mut file = File::open(path)?;
+    let mut buff = String::new();
+    file.read_to_string(&mut buff)?;
+    Ok(serde_json::from_str(&buff)?)
+}
+
+fn preset_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or(path.to_string_lossy().to_string())
+}
+
+/// The sound root, then the folder of the sounds added by the user
+pub fn sound_folders(sound_root: &str, dirs: &Dirs) -> Vec<PathBuf> {
+    let mut folders = vec![PathBuf::from(sound_root)];
+    if !folders.contains(&dirs.sounds()) {
+        folders.push(dirs.sounds());
+    }
+    folders
+}
+
+/// Sounds of the folders, those of each category folder then the loose files,
+/// whose category is the name of the folder, named after the file or by the manifest
+pub fn load_available_sounds(folders: &[PathBuf], volumes: &Volumes) -> Vec<Sound> {
+    let mut sounds = vec![];
+    for folder in folders {
+        let mut categories: Vec<PathBuf> = match std::fs::read_dir(folder) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir())
+                .collect(),
+            Err(e) => {
+                debug!("No sounds in {}: {}", folder.display(), e);
+                continue;
+            }
+        };
+        categories.sort();
+        categories.push(folder.clone());
+        let manifest = Manifest::load(folder).unwrap_or_else(|e| {
+            warn!("{}", e);
+            Manifest::default()
+        });
+        for dir in categories {
+            let category = match dir.file_name() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => "sounds".to_string(),
+            };
+            for path in sound_files(&dir) {
+                let Some(filename) = path.file_name().and_then(|n| n.to_str()) else {
+                    continue;
+                };
+                let name = filename.rsplit_once('.').map_or(filename, |(name, _)| name);
+                let volume = volumes.get(&category, name);
+                let file = path.strip_prefix(folder).unwrap_or(&path).to_string_lossy();
+                let name = manifest.name(&file).unwrap_or(filename);
+                sounds.push(Sound::new(name, &path.to_string_lossy(), &category, volume));
+            }
+        }
+    }
+    sounds
+}
+
+/// Files of a folder rodio can decode, sorted, the ones with a non UTF-8 path being skipped
+pub fn sound_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .filter(|path| {
+                path.extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| SOUND_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+            })
+            .filter(|path| {
+                let valid = path.to_str().is_some();
+                if !valid {
+                    warn!("Skipping {}, its path is not valid UTF-8", path.display());
+                }
+                valid
+            })
+            .collect(),
+        Err(_) => vec![],
+    };
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_imported_sounds_by_name_and_file() {
+        let dir = std::env::temp_dir().join(format!("serenit-find-{}", std::process::id()));
+        let dirs = Dirs::in_dir(&dir);
+        let category = dirs.sounds().join("rain");
+        std::fs::create_dir_all(&category).unwrap();
+        std::fs::write(category.join("rain-on-tin-roof.wav"), b"").unwrap();
+        std::fs::write(category.join("drizzle.mp3"), b"").unwrap();
+        let manifest = r#"{"sounds": [{"name": "Rain on tin roof", "category": "rain",
+            "file": "rain/rain-on-tin-roof.wav", "source": "/tmp/roof.wav"}]}"#;
+        std::fs::write(Manifest::path(&dirs.sounds()), manifest).unwrap();
+        let sm = SoundManager::new(SoundManagerOptions {
+            sound_root: dir.join("none").to_string_lossy().to_string(),
+            dirs,
+            silent: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let roof = category.join("rain-on-tin-roof.wav").to_string_lossy().to_string();
+        for key in ["Rain on tin roof", "rain-on-tin-roof", roof.as_str()] {
+            assert_eq!(sm.find_sound(key).map(|s| s.path()), Some(roof.as_str()), "{key}");
+        }
+        for key in ["drizzle", "drizzle.mp3"] {
+            assert_eq!(sm.find_sound(key).map(|s| s.name()), Some("drizzle.mp3"), "{key}");
+        }
+        assert!(sm.find_sound("rain-on-tin").is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
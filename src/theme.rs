@@ -0,0 +1,224 @@
+use ratatui::style::{
+    palette::tailwind::{AMBER, SKY, SLATE, TEAL},
+    Color, Modifier, Style,
+};
+use serde::Deserialize;
+
+/// Colours of the user interface
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    /// Background of the mixer and of the even rows
+    pub background: Color,
+    /// Background of the odd rows
+    pub alt_background: Color,
+    /// Background of the sounds list and of the tabs
+    pub panel: Color,
+    /// Header, footer and mixer border
+    pub bar: Color,
+    pub bar_text: Color,
+    pub text: Color,
+    /// Playing sounds, prompts and focused titles
+    pub accent: Color,
+    pub selection: Color,
+    pub tag: Color,
+    /// Characters matching the search
+    pub matched: Color,
+    /// Empty part of the volume bars
+    pub dim: Color,
+}
+
+pub const TEAL_THEME: Theme = Theme {
+    background: TEAL.c900,
+    alt_background: TEAL.c800,
+    panel: TEAL.c800,
+    bar: TEAL.c500,
+    bar_text: AMBER.c100,
+    text: TEAL.c100,
+    accent: AMBER.c100,
+    selection: TEAL.c600,
+    tag: TEAL.c300,
+    matched: AMBER.c300,
+    dim: TEAL.c800,
+};
+
+pub const SLATE_THEME: Theme = Theme {
+    background: SLATE.c900,
+    alt_background: SLATE.c800,
+    panel: SLATE.c800,
+    bar: SKY.c700,
+    bar_text: SKY.c50,
+    text: SLATE.c100,
+    accent: SKY.c300,
+    selection: SLATE.c600,
+    tag: SLATE.c400,
+    matched: AMBER.c300,
+    dim: SLATE.c700,
+};
+
+pub const HIGH_CONTRAST_THEME: Theme = Theme {
+    background: Color::Black,
+    alt_background: Color::Black,
+    panel: Color::Black,
+    bar: Color::White,
+    bar_text: Color::Black,
+    text: Color::White,
+    accent: Color::LightYellow,
+    selection: Color::Blue,
+    tag: Color::LightCyan,
+    matched: Color::LightGreen,
+    dim: Color::DarkGray,
+};
+
+/// Only uses black, white and the two greys of the 16 colours terminals
+pub const MONO_THEME: Theme = Theme {
+    background: Color::Black,
+    alt_background: Color::Black,
+    panel: Color::Black,
+    bar: Color::Gray,
+    bar_text: Color::Black,
+    text: Color::White,
+    accent: Color::White,
+    selection: Color::DarkGray,
+    tag: Color::Gray,
+    matched: Color::White,
+    dim: Color::DarkGray,
+};
+
+/// The terminal colours, used when `NO_COLOR` is set
+pub const NO_COLOR_THEME: Theme = Theme {
+    background: Color::Reset,
+    alt_background: Color::Reset,
+    panel: Color::Reset,
+    bar: Color::Reset,
+    bar_text: Color::Reset,
+    text: Color::Reset,
+    accent: Color::Reset,
+    selection: Color::Reset,
+    tag: Color::Reset,
+    matched: Color::Reset,
+    dim: Color::Reset,
+};
+
+pub const BUILTIN_THEMES: [(&str, Theme); 5] = [
+    ("teal", TEAL_THEME),
+    ("slate", SLATE_THEME),
+    ("high-contrast", HIGH_CONTRAST_THEME),
+    ("mono", MONO_THEME),
+    ("none", NO_COLOR_THEME),
+];
+
+impl Default for Theme {
+    fn default() -> Self {
+        TEAL_THEME
+    }
+}
+
+impl Theme {
+    pub fn builtin(name: &str) -> Option<Theme> {
+        BUILTIN_THEMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, theme)| *theme)
+    }
+
+    /// Titles and tabs
+    pub fn header(&self) -> Style {
+        Style::new()
+            .fg(self.text)
+            .bg(self.panel)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    pub fn borders(&self) -> Style {
+        Style::new()
+            .fg(self.text)
+            .bg(self.bar)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    pub fn selected(&self) -> Style {
+        let style = Style::new()
+            .bg(self.selection)
+            .add_modifier(Modifier::BOLD);
+        // Without colours the selection is shown in reverse video
+        if self.selection == Color::Reset {
+            style.add_modifier(Modifier::REVERSED)
+        } else {
+            style
+        }
+    }
+
+    pub fn matched(&self) -> Style {
+        Style::new()
+            .fg(self.matched)
+            .add_modifier(Modifier::UNDERLINED)
+    }
+
+    pub fn tag(&self) -> Style {
+        Style::new().fg(self.tag)
+    }
+
+    /// Alternating background of the list rows
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn row(&self, i: usize) -> Color {
+        if i % 2 == 0 {
+            self.background
+        } else {
+            self.alt_background
+        }
+    }
+}
+
+/// A `[themes.<name>]` table of `config.toml`, the colours it changes in a built-in theme
+///
+/// ```toml
+/// [themes.night]
+/// base = "slate"
+/// accent = "#ffb86c"
+/// selection = "blue"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    /// Built-in theme giving the other colours, teal by default
+    pub base: Option<String>,
+    pub background: Option<Color>,
+    pub alt_background: Option<Color>,
+    pub panel: Option<Color>,
+    pub bar: Option<Color>,
+    pub bar_text: Option<Color>,
+    pub text: Option<Color>,
+    pub accent: Option<Color>,
+    pub selection: Option<Color>,
+    pub tag: Option<Color>,
+    pub matched: Option<Color>,
+    pub dim: Option<Color>,
+}
+
+impl ThemeConfig {
+    /// The base theme with the colours of this table, None if the base is unknown
+    pub fn theme(&self) -> Option<Theme> {
+        let base = match &self.base {
+            Some(name) => Theme::builtin(name)?,
+            None => Theme::default(),
+        };
+        Some(Theme {
+            background: self.background.unwrap_or(base.background),
+            alt_background: self.alt_background.unwrap_or(base.alt_background),
+            panel: self.panel.unwrap_or(base.panel),
+            bar: self.bar.unwrap_or(base.bar),
+            bar_text: self.bar_text.unwrap_or(base.bar_text),
+            text: self.text.unwrap_or(base.text),
+            accent: self.accent.unwrap_or(base.accent),
+            selection: self.selection.unwrap_or(base.selection),
+            tag: self.tag.unwrap_or(base.tag),
+            matched: self.matched.unwrap_or(base.matched),
+            dim: self.dim.unwrap_or(base.dim),
+        })
+    }
+}
+
+/// Whether colours are disabled, see https://no-color.org
+pub fn no_color() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
@@ -0,0 +1,136 @@
+use crate::fuzzy::fuzzy_match;
+use crate::library::{tags_match, Library};
+use crate::sound::Sound;
+
+/// A visible row of the sounds list
+pub struct Row {
+    /// Path of the sound, it identifies it whatever the filter
+    pub path: String,
+    pub name: String,
+    pub category: String,
+    pub tags: Vec<String>,
+    /// Chars matching the search
+    pub name_matches: Vec<usize>,
+    pub category_matches: Vec<usize>,
+    /// Index of the tag matching the search
+    pub tag_match: Option<usize>,
+}
+
+/// Sounds shown outside of a search
+pub enum Scope<'a> {
+    All,
+    /// Sounds of a category folder
+    Category(&'a str),
+    /// Given sounds in the given order (favourites, recent...)
+    Sounds(&'a [String]),
+    /// Sounds whose tags match a filter, see `tags_match`
+    Tags(&'a str),
+}
+
+/// What the sounds list shows
+pub struct Filter<'a> {
+    pub scope: Scope<'a>,
+    pub search: Option<&'a str>,
+    /// Narrows the scope to the sounds matching these tags
+    pub tags: Option<&'a str>,
+}
+
+/// Rows of the sounds list, maps the visible indexes to the sounds
+pub struct ListView {
+    rows: Vec<Row>,
+}
+
+impl ListView {
+    /// The search results, or the sounds of the category
+    pub fn new(sounds: &[Sound], library: &Library, filter: &Filter) -> ListView {
+        let row = |s: &Sound| Row {
+            path: s.path().to_string(),
+            name: s.name().to_string(),
+            category: s.category().to_uppercase(),
+            tags: library.tags(s.path()).to_vec(),
+            name_matches: vec![],
+            category_matches: vec![],
+            tag_match: None,
+        };
+        let tagged = |s: &&Sound| {
+            filter
+                .tags
+                .is_none_or(|tags| tags_match(tags, library.tags(s.path())))
+        };
+        let rows = match filter.search {
+            Some(search) if !search.trim().is_empty() => {
+                let mut results: Vec<(i32, Row)> = sounds
+                    .iter()
+                    .filter(tagged)
+                    .filter_map(|s| {
+                        let mut row = row(s);
+                        // The best of the name, category and tags matches
+                        let mut best = None;
+                        if let Some((score, m)) = fuzzy_match(search, s.name()) {
+                            best = Some(score);
+                            row.name_matches = m;
+                        }
+                        if let Some((score, m)) = fuzzy_match(search, s.category()) {
+                            if best.is_none_or(|b| score > b) {
+                                best = Some(score);
+                                row.name_matches.clear();
+                                row.category_matches = m;
+                            }
+                        }
+                        for (i, tag) in row.tags.iter().enumerate() {
+                            if let Some((score, _)) = fuzzy_match(search, tag) {
+                                if best.is_none_or(|b| score > b) {
+                                    best = Some(score);
+                                    row.name_matches.clear();
+                                    row.category_matches.clear();
+                                    row.tag_match = Some(i);
+                                }
+                            }
+                        }
+                        best.map(|score| (score, row))
+                    })
+                    .collect();
+                results.sort_by_key(|(score, _)| -score);
+                results.into_iter().map(|(_, row)| row).collect()
+            }
+            _ => {
+                let scoped: Vec<&Sound> = match filter.scope {
+                    Scope::All => sounds.iter().collect(),
+                    Scope::Category(category) => {
+                        sounds.iter().filter(|s| s.category() == category).collect()
+                    }
+                    Scope::Sounds(paths) => paths
+                        .iter()
+                        .filter_map(|path| sounds.iter().find(|s| s.path() == path))
+                        .collect(),
+                    Scope::Tags(expression) => sounds
+                        .iter()
+                        .filter(|s| tags_match(expression, library.tags(s.path())))
+                        .collect(),
+                };
+                scoped.into_iter().filter(tagged).map(row).collect()
+            }
+        };
+        ListView { rows }
+    }
+
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn path(&self, index: usize) -> Option<&str> {
+        self.rows.get(index).map(|row| row.path.as_str())
+    }
+
+    pub fn index_of(&self, path: &str) -> Option<usize> {
+        self.rows.iter().position(|row| row.path == path)
+    }
+}
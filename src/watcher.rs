@@ -0,0 +1,93 @@
+//! Watches the sound folders so that the files dropped in them or removed show up
+//! without restarting, by sending `rescan` to the owner of the sound manager.
+
+use crate::control::ControlRequest;
+use cli_log::*;
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+
+/// Watches the folders and their category folders in the background
+#[cfg(target_os = "linux")]
+pub fn start(folders: Vec<PathBuf>, tx: Sender<ControlRequest>) {
+    use crate::control::{self, Command};
+    use nix::errno::Errno;
+    use nix::sys::inotify::{InitFlags, Inotify};
+    use std::thread;
+    use std::time::Duration;
+
+    // Events closer than this are handled at once, copying a file gives many of them
+    const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+    let inotify = match Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC) {
+        Ok(inotify) => inotify,
+        Err(e) => {
+            warn!("Cannot watch the sound folders: {}", e);
+            return;
+        }
+    };
+    thread::spawn(move || {
+        let mut missing = watch(&inotify, &folders);
+        let mut changed = false;
+        loop {
+            thread::sleep(SETTLE_TIME);
+            match inotify.read_events() {
+                Ok(events) => changed |= !events.is_empty(),
+                Err(Errno::EAGAIN) => {
+                    // A folder missing at first may have been created since
+                    if missing > 0 {
+                        let now = watch(&inotify, &folders);
+                        changed |= now < missing;
+                        missing = now;
+                    }
+                    if changed {
+                        changed = false;
+                        // New category folders are watched too
+                        missing = watch(&inotify, &folders);
+                        control::send(&tx, Command::Rescan);
+                    }
+                }
+                Err(e) => {
+                    warn!("Stopped watching the sound folders: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+}
+
+// Watches the folders and their subfolders, already watched ones being left as they are,
+// gives the number of folders that do not exist
+#[cfg(target_os = "linux")]
+fn watch(inotify: &nix::sys::inotify::Inotify, folders: &[PathBuf]) -> usize {
+    use nix::sys::inotify::AddWatchFlags;
+
+    let flags = AddWatchFlags::IN_CLOSE_WRITE
+        | AddWatchFlags::IN_CREATE
+        | AddWatchFlags::IN_DELETE
+        | AddWatchFlags::IN_MOVED_FROM
+        | AddWatchFlags::IN_MOVED_TO
+        | AddWatchFlags::IN_DELETE_SELF;
+    let mut missing = 0;
+    for folder in folders {
+        let Ok(entries) = std::fs::read_dir(folder) else {
+            missing += 1;
+            continue;
+        };
+        let dirs = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir());
+        for dir in std::iter::once(folder.clone()).chain(dirs) {
+            if let Err(e) = inotify.add_watch(&dir, flags) {
+                warn!("Cannot watch {}: {}", dir.display(), e);
+            }
+        }
+    }
+    missing
+}
+
+/// Only inotify is supported, `serenit send rescan` reloads the folders elsewhere
+#[cfg(not(target_os = "linux"))]
+pub fn start(_folders: Vec<PathBuf>, _tx: Sender<ControlRequest>) {
+    info!("The sound folders are not watched on this system");
+}